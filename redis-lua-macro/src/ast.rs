@@ -0,0 +1,122 @@
+use full_moon::{
+    ast::{Call, Expression, FunctionArgs, FunctionCall, Index, Prefix, Suffix, Value, Var},
+    node::Node,
+    tokenizer::{Symbol, TokenReference, TokenType},
+};
+
+/// Byte range of the node in the lua script.
+pub fn range<N: Node>(node: &N) -> (usize, usize) {
+    node.range()
+        .map(|(start, end)| (start.bytes(), end.bytes()))
+        .unwrap_or((0, 0))
+}
+
+/// The identifier of the token, e.g. `x`
+pub fn ident(token: &TokenReference) -> Option<String> {
+    match &*token.token_type() {
+        TokenType::Identifier { identifier } => Some(identifier.to_string()),
+        _ => None,
+    }
+}
+
+/// The contents of the string literal, e.g. `get` for `"get"`
+pub fn string(token: &TokenReference) -> Option<String> {
+    match &*token.token_type() {
+        TokenType::StringLiteral { literal, .. } => Some(literal.to_string()),
+        _ => None,
+    }
+}
+
+/// Strip parentheses and return the value if the expression is a single value.
+pub fn value<'a, 'b>(expr: &'b Expression<'a>) -> Option<&'b Value<'a>> {
+    match expr {
+        Expression::Parentheses { expression, .. } => value(expression),
+        Expression::Value { value, binop: None } => Some(value),
+        _ => None,
+    }
+}
+
+/// `true` if the expression is the literal `nil`.
+pub fn is_nil(expr: &Expression) -> bool {
+    match value(expr) {
+        Some(Value::Symbol(token)) => match &*token.token_type() {
+            TokenType::Symbol { symbol } => *symbol == Symbol::Nil,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// The variable name if the expression is a plain variable, e.g. `x`
+pub fn var_name(expr: &Expression) -> Option<String> {
+    match value(expr) {
+        Some(Value::Var(Var::Name(token))) => ident(token),
+        _ => None,
+    }
+}
+
+/// The function call if the expression is a single call.
+pub fn call<'a, 'b>(expr: &'b Expression<'a>) -> Option<&'b FunctionCall<'a>> {
+    match value(expr) {
+        Some(Value::FunctionCall(call)) => Some(call),
+        _ => None,
+    }
+}
+
+/// The field name of `_G.name` or `_G["name"]`.
+///
+/// Returns `Some(None)` if `_G` is indexed by an expression other than a string literal.
+pub fn global_field<'a, 'b, I>(prefix: &'b Prefix<'a>, mut suffixes: I) -> Option<Option<String>>
+where
+    I: Iterator<Item = &'b Suffix<'a>>,
+    'a: 'b,
+{
+    match prefix {
+        Prefix::Name(name) if ident(name).as_deref() == Some("_G") => {}
+        _ => return None,
+    }
+
+    match suffixes.next()? {
+        Suffix::Index(Index::Dot { name, .. }) => Some(ident(name)),
+        Suffix::Index(Index::Brackets { expression, .. }) => match value(expression) {
+            Some(Value::String(token)) => Some(string(token)),
+            _ => Some(None),
+        },
+        Suffix::Call(_) => None,
+    }
+}
+
+/// The arguments of `redis.call(...)` or `redis.pcall(...)`.
+pub fn redis_call<'a, 'b>(call: &'b FunctionCall<'a>) -> Option<Vec<&'b Expression<'a>>> {
+    match call.prefix() {
+        Prefix::Name(name) if ident(name).as_deref() == Some("redis") => {}
+        _ => return None,
+    }
+
+    let mut suffixes = call.iter_suffixes();
+
+    match suffixes.next() {
+        Some(Suffix::Index(Index::Dot { name, .. }))
+            if matches!(ident(name).as_deref(), Some("call") | Some("pcall")) => {}
+        _ => return None,
+    }
+
+    match (suffixes.next(), suffixes.next()) {
+        (
+            Some(Suffix::Call(Call::AnonymousCall(FunctionArgs::Parentheses {
+                arguments, ..
+            }))),
+            None,
+        ) => Some(arguments.iter().collect()),
+        _ => None,
+    }
+}
+
+/// The command name of `redis.call("cmd", ...)` in lower case if it's a string literal.
+pub fn redis_command(call: &FunctionCall) -> Option<String> {
+    let args = redis_call(call)?;
+    match value(args.get(0)?) {
+        Some(Value::String(token)) => string(token).map(|s| s.to_lowercase()),
+        _ => None,
+    }
+}
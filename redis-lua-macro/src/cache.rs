@@ -0,0 +1,223 @@
+//! Cache of lint results across builds.
+//!
+//! Linting a script is deterministic given the script text, the standard library,
+//! the arguments, the lint code and the versions of the linter, so the results are stored
+//! in a file named after the hash of them. Unchanged scripts skip linting on the next build.
+//! Files not used for 30 days are removed.
+
+use crate::{check::Diag, limits::Usage};
+use serde::{Deserialize, Serialize};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    sync::Once,
+    time::{Duration, SystemTime},
+};
+
+/// The manifest pinning the versions of the linter and the parser.
+const MANIFEST: &str = include_str!("../Cargo.toml");
+
+/// The sources producing the lint results, so that a change to them within a release
+/// doesn't reuse the old results.
+const LINT_SOURCES: &[&str] = &[
+    include_str!("ast.rs"),
+    include_str!("cache.rs"),
+    include_str!("check.rs"),
+    include_str!("globals.rs"),
+    include_str!("lexer.rs"),
+    include_str!("limits.rs"),
+    include_str!("nil.rs"),
+    include_str!("syntax.rs"),
+    include_str!("selene.toml"),
+    include_str!("redis.toml"),
+];
+
+/// Cache files not used for this duration are removed.
+const MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Lint results of a script.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Entry {
+    pub usage: Usage,
+    pub diags: Vec<Diag>,
+}
+
+/// The directory to store the cache.
+///
+/// `REDIS_LUA_CACHE_DIR` takes precedence, then `OUT_DIR` of the crate being compiled
+/// and the target directory. An empty `REDIS_LUA_CACHE_DIR` disables the cache.
+fn dir() -> Option<PathBuf> {
+    let base = match env::var_os("REDIS_LUA_CACHE_DIR") {
+        Some(dir) if dir.is_empty() => return None,
+        Some(dir) => PathBuf::from(dir),
+        None => env::var_os("OUT_DIR")
+            .or_else(|| env::var_os("CARGO_TARGET_DIR"))
+            .map(PathBuf::from)
+            .or_else(|| {
+                let manifest = PathBuf::from(env::var_os("CARGO_MANIFEST_DIR")?);
+                Some(workspace(&manifest).join("target")).filter(|t| t.is_dir())
+            })?,
+    };
+    Some(base.join("redis-lua-lint"))
+}
+
+/// The root of the workspace containing the package, which holds the target directory.
+fn workspace(manifest: &Path) -> &Path {
+    manifest
+        .ancestors()
+        .find(|dir| {
+            fs::read_to_string(dir.join("Cargo.toml"))
+                .map_or(false, |toml| toml.contains("[workspace]"))
+        })
+        .unwrap_or(manifest)
+}
+
+/// The version requirement of the dependency in the manifest, e.g. `=0.5.3`.
+fn pinned(name: &str) -> String {
+    let manifest: toml::Value = match toml::from_str(MANIFEST) {
+        Ok(manifest) => manifest,
+        Err(_) => return String::new(),
+    };
+    match manifest.get("dependencies").and_then(|deps| deps.get(name)) {
+        Some(toml::Value::String(version)) => version.clone(),
+        Some(dep) => dep
+            .get("version")
+            .and_then(|version| version.as_str())
+            .unwrap_or_default()
+            .into(),
+        None => String::new(),
+    }
+}
+
+/// The cache key of the script.
+///
+/// `defined` are the globals added to the standard library. Each part is prefixed
+/// by its length so that different inputs never produce the same hashed bytes.
+pub fn key(script: &str, defined: &[String], args: &[String]) -> String {
+    let mut hasher = sha1::Sha1::new();
+    let mut update = |part: &str| {
+        hasher.update(&(part.len() as u64).to_le_bytes());
+        hasher.update(part.as_bytes());
+    };
+
+    update(env!("CARGO_PKG_VERSION"));
+    update(&pinned("selene-lib"));
+    update(&pinned("full_moon"));
+    for source in LINT_SOURCES {
+        update(source);
+    }
+    for list in &[defined, args] {
+        update(&list.len().to_string());
+        for item in list.iter() {
+            update(item);
+        }
+    }
+    update(script);
+
+    hasher.digest().to_string()
+}
+
+pub fn load(key: &str) -> Option<Entry> {
+    let path = dir()?.join(format!("{}.toml", key));
+    let entry = toml::from_str(&fs::read_to_string(&path).ok()?).ok()?;
+
+    // Mark the file as used so that it's not pruned.
+    let _ = fs::OpenOptions::new()
+        .write(true)
+        .open(&path)
+        .and_then(|file| file.set_modified(SystemTime::now()));
+
+    Some(entry)
+}
+
+/// Remove the files not used for `MAX_AGE`, including temporary files left by
+/// interrupted builds. Done once per process as the directory may be large.
+fn prune(dir: &Path) {
+    static PRUNE: Once = Once::new();
+
+    PRUNE.call_once(|| {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        let now = SystemTime::now();
+
+        for entry in entries.flatten() {
+            let old = entry
+                .metadata()
+                .and_then(|meta| meta.modified())
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok())
+                .map_or(false, |age| age > MAX_AGE);
+            if old {
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+    });
+}
+
+/// Store the results; failures are ignored as the cache is only an optimization.
+pub fn store(key: &str, entry: &Entry) {
+    let dir = match dir() {
+        Some(dir) => dir,
+        None => return,
+    };
+    // Converting into `Value` first puts tables after values as TOML requires.
+    let content = match toml::Value::try_from(entry).map(|v| v.to_string()) {
+        Ok(content) => content,
+        Err(_) => return,
+    };
+
+    // Write to a temporary file first not to expose a partial file to parallel builds.
+    let temp = dir.join(format!("{}.{}.tmp", key, std::process::id()));
+    let _ = fs::create_dir_all(&dir)
+        .and_then(|_| fs::write(&temp, content))
+        .and_then(|_| fs::rename(&temp, dir.join(format!("{}.toml", key))));
+    let _ = fs::remove_file(&temp);
+
+    prune(&dir);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|item| item.to_string()).collect()
+    }
+
+    #[test]
+    fn pinned_versions() {
+        // The results depend on the exact versions of the linter and the parser.
+        assert!(pinned("selene-lib").starts_with('='));
+        assert!(pinned("full_moon").starts_with('='));
+    }
+
+    #[test]
+    fn stable_key() {
+        let k = key("return 1", &strings(&["a"]), &strings(&["b"]));
+        assert_eq!(k.len(), 40);
+        assert_eq!(k, key("return 1", &strings(&["a"]), &strings(&["b"])));
+    }
+
+    #[test]
+    fn distinct_keys() {
+        let base = key("return 1", &[], &[]);
+        assert_ne!(base, key("return 2", &[], &[]));
+        assert_ne!(
+            key("s", &strings(&["a", "b"]), &[]),
+            key("s", &strings(&["ab"]), &[])
+        );
+        assert_ne!(
+            key("s", &strings(&["a"]), &[]),
+            key("s", &[], &strings(&["a"]))
+        );
+        assert_ne!(key("s", &strings(&["a"]), &[]), key("as", &[], &[]));
+    }
+
+    #[test]
+    fn workspace_root() {
+        let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+        assert_eq!(workspace(manifest), manifest.parent().unwrap());
+    }
+}
@@ -96,7 +96,7 @@ impl<'a> Chain<'a> {
             let bounds_life = self.bounds_life();
 
             quote! {
-                fn invoke<T>(self, con: &mut dyn redis_lua::redis::ConnectionLike) -> redis_lua::redis::RedisResult<T>
+                fn invoke<T>(self, con: &mut dyn redis_lua::redis::ConnectionLike) -> Result<T, redis_lua::Error>
                 where
                     T: redis_lua::redis::FromRedisValue,
                     I: redis_lua::Script,
@@ -106,7 +106,7 @@ impl<'a> Chain<'a> {
                     redis_lua::Script::invoke(self, con)
                 }
 
-                fn invoke_async<'a, C, T>(self, con: &'a mut C) -> redis_lua::redis::RedisFuture<'a, T>
+                fn invoke_async<'a, C, T>(self, con: &'a mut C) -> redis_lua::futures::future::BoxFuture<'a, Result<T, redis_lua::Error>>
                 where
                     C: redis_lua::redis::aio::ConnectionLike + Send,
                     T: redis_lua::redis::FromRedisValue + Send,
@@ -209,6 +209,14 @@ impl<'a> Chain<'a> {
                     info.push(self.info.clone());
                     #(#args)*
                 }
+
+                fn route_keys(&self, keys: &mut Vec<Vec<u8>>) {
+                    self.inner.route_keys(keys);
+                }
+
+                fn invoke_options(&self) -> Option<&redis_lua::Options> {
+                    self.inner.invoke_options()
+                }
             }
         }
     }
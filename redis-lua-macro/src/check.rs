@@ -1,79 +1,395 @@
-use crate::{file::as_path, proc_macro::Span, script::Script};
+use crate::{
+    cache, globals,
+    lexer::{self, lex, Kind, Lexeme},
+    limits::{self, Usage},
+    nil,
+    script::Script,
+    syntax,
+};
 use full_moon::{
     ast::{owned::Owned, AstError},
-    tokenizer::Token,
+    tokenizer::{Token, TokenType, TokenizerError},
     Error as ParseError,
 };
+use proc_macro2::{Span, TokenStream};
 use proc_macro_error::{Diagnostic as PDiagnostic, Level as PLevel};
+use quote::{quote, quote_spanned};
 use selene_lib::{
-    rules::Severity, standard_library::StandardLibrary, Checker as SeleneChecker, CheckerConfig,
-    CheckerDiagnostic,
+    rules::Severity,
+    standard_library::{Field, StandardLibrary},
+    Checker as SeleneChecker, CheckerConfig, CheckerDiagnostic,
 };
-use std::include_str;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, include_str, sync::OnceLock};
+
+/// Emit a warning at the span on stable Rust as well.
+///
+/// `proc_macro_error` emits warnings only on nightly, so the warning is raised
+/// by the compiler as the use of a deprecated item carrying the message.
+fn warning(span: Option<Span>, msg: String) -> TokenStream {
+    let span = span.unwrap_or_else(Span::call_site);
+    let item = quote_spanned! { span => LuaWarning };
 
-fn convert_level(l: Severity) -> PLevel {
-    match l {
-        Severity::Error => PLevel::Error,
-        #[cfg(unstable)]
-        Severity::Warning => PLevel::Warning,
-        #[cfg(not(unstable))]
-        Severity::Warning => PLevel::Error,
+    quote! {
+        {
+            #[deprecated(note = #msg)]
+            struct LuaWarning;
+            let _ = #item;
+        }
     }
 }
 
-fn emit_diag_one(span: Vec<Span>, cd: CheckerDiagnostic) {
-    let d = cd.diagnostic;
-    let msg = format!("in lua: {} ({})", d.message, d.code);
+/// Note attached to a part of the script.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SpanNote {
+    pub range: (usize, usize),
+    pub message: String,
+}
 
-    let pd = match span.get(0).cloned() {
-        Some(span) => PDiagnostic::spanned(span.into(), convert_level(cd.severity), msg),
-        None => PDiagnostic::new(convert_level(cd.severity), msg),
-    };
-    let pd = d
-        .notes
-        .iter()
-        .fold(pd, |pd, note| pd.note(note.to_string()));
-    pd.emit()
+/// Diagnostic to be emitted as a compiler message.
+///
+/// The ranges are in the lua script, so that diagnostics are emitted
+/// at the right spans even when restored from the lint cache.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Diag {
+    pub range: (usize, usize),
+    pub error: bool,
+    pub message: String,
+    pub notes: Vec<String>,
+    pub span_notes: Vec<SpanNote>,
 }
 
-fn emit_parse_err(script: &Script, msg: &str, token: Option<&Token>) {
-    let range = match token {
-        Some(token) => (token.start_position().bytes(), token.end_position().bytes()),
-        None => (0, script.script().len()),
-    };
-    let spans = script.range_to_span(range);
+impl Diag {
+    fn new(range: (usize, usize), severity: Severity, message: String) -> Self {
+        Self {
+            range,
+            error: matches!(severity, Severity::Error),
+            message,
+            notes: vec![],
+            span_notes: vec![],
+        }
+    }
+
+    /// Emit the error, or return the code to raise the warning.
+    fn emit(self, script: &Script) -> TokenStream {
+        let span = script.range_to_span(self.range).get(0).cloned();
+
+        if !self.error {
+            let notes = self.span_notes.into_iter().map(|note| note.message);
+            let msg = std::iter::once(self.message)
+                .chain(notes)
+                .chain(self.notes)
+                .collect::<Vec<_>>()
+                .join("\n  = note: ");
+            return warning(span.map(Into::into), msg);
+        }
+
+        let pd = match span {
+            Some(span) => PDiagnostic::spanned(span.into(), PLevel::Error, self.message),
+            None => PDiagnostic::new(PLevel::Error, self.message),
+        };
+        let pd = self.span_notes.into_iter().fold(pd, |pd, note| {
+            match script.range_to_span(note.range).get(0).cloned() {
+                Some(span) => pd.span_note(span.into(), note.message),
+                None => pd.note(note.message),
+            }
+        });
+        let pd = self.notes.into_iter().fold(pd, |pd, note| pd.note(note));
+        pd.emit();
+        TokenStream::new()
+    }
+}
+
+impl From<CheckerDiagnostic> for Diag {
+    fn from(cd: CheckerDiagnostic) -> Self {
+        let d = cd.diagnostic;
+        let label = d.primary_label.range;
+        let msg = format!("in lua: {} ({})", d.message, d.code);
+
+        let mut diag = Diag::new((label.0 as usize, label.1 as usize), cd.severity, msg);
+        diag.notes = d.notes.iter().map(|note| note.to_string()).collect();
+        diag
+    }
+}
+
+/// Diagnostic of the lints implemented in this crate.
+pub struct Lint {
+    pub range: (usize, usize),
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub notes: Vec<String>,
+}
+
+impl From<Lint> for Diag {
+    fn from(lint: Lint) -> Self {
+        let msg = format!("in lua: {} ({})", lint.message, lint.code);
+
+        let mut diag = Diag::new(lint.range, lint.severity, msg);
+        diag.notes = lint.notes;
+        diag
+    }
+}
+
+fn rust_syntax(t: &Lexeme, severity: Severity, message: String, fix: String) -> Lint {
+    Lint {
+        range: t.range,
+        severity,
+        code: "rust_syntax",
+        message,
+        notes: vec![fix],
+    }
+}
+
+/// Detects Rust syntax written in lua, which otherwise ends up with "unexpected token".
+fn rustisms(script: &str) -> Vec<Lint> {
+    let tokens = lex(script);
+    let declares_null = tokens
+        .windows(2)
+        .any(|w| w[0].is("local") && w[1].is("null"));
+
+    let mut lints = Vec::new();
+
+    for (i, t) in tokens.iter().enumerate() {
+        let prev = if i > 0 { tokens.get(i - 1) } else { None };
+        let next = tokens.get(i + 1);
+        let is_string = |t: Option<&Lexeme>| t.map(|t| t.kind) == Some(Kind::String);
+
+        let operator = |lua: &str| {
+            rust_syntax(
+                t,
+                Severity::Error,
+                format!("`{}` is not a lua operator", t.text),
+                format!("try `{}`", lua),
+            )
+        };
+
+        let lint = match t.text {
+            _ if t.kind == Kind::String => None,
+            "!=" => Some(operator("~=")),
+            "&&" => Some(operator("and")),
+            "||" => Some(operator("or")),
+            "!" => Some(operator("not")),
+            "+=" | "-=" | "*=" | "/=" | "%=" => {
+                let var = prev.map(|p| p.text).unwrap_or("x");
+                let op = &t.text[..1];
+                Some(rust_syntax(
+                    t,
+                    Severity::Error,
+                    format!("compound assignment `{}` is not available in lua", t.text),
+                    format!("try `{0} = {0} {1} ...`", var, op),
+                ))
+            }
+            "+" if is_string(prev) || is_string(next) => Some(rust_syntax(
+                t,
+                Severity::Warning,
+                "`+` does not concatenate strings in lua".into(),
+                "try `..`".into(),
+            )),
+            "null"
+                if t.kind == Kind::Name
+                    && !declares_null
+                    && !prev.map_or(false, |p| p.is(".") || p.is(":")) =>
+            {
+                Some(rust_syntax(
+                    t,
+                    Severity::Error,
+                    "`null` is not defined in lua".into(),
+                    "try `nil`".into(),
+                ))
+            }
+            "[" if prev.map_or(false, |p| p.kind == Kind::Name || p.is("]") || p.is(")"))
+                && next.map_or(false, |n| n.kind == Kind::Number && n.text == "0")
+                && tokens.get(i + 2).map_or(false, |n| n.is("]")) =>
+            {
+                Some(rust_syntax(
+                    t,
+                    Severity::Warning,
+                    "lua arrays start at index 1, not 0".into(),
+                    "try `[1]` for the first element".into(),
+                ))
+            }
+            "." if next.map_or(false, |n| n.is("len"))
+                && tokens.get(i + 2).map_or(false, |n| n.is("("))
+                && tokens.get(i + 3).map_or(false, |n| n.is(")")) =>
+            {
+                let var = prev.map(|p| p.text).unwrap_or("x");
+                // `t.len()` is valid if `len` is a field holding a function.
+                Some(rust_syntax(
+                    t,
+                    Severity::Warning,
+                    "`.len()` calls the field `len` in lua, not the length".into(),
+                    format!("try `#{}`", var),
+                ))
+            }
+            _ => None,
+        };
+
+        lints.extend(lint);
+    }
+
+    lints
+}
+
+/// Openers of blocks and brackets which are not closed before `pos`,
+/// with the token which is expected next.
+fn unclosed<'a>(tokens: &[Lexeme<'a>], pos: usize) -> Vec<(Lexeme<'a>, &'static str)> {
+    let mut stack: Vec<(Lexeme, &'static str)> = Vec::new();
+
+    for t in tokens.iter().take_while(|t| t.range.0 < pos) {
+        let top = stack.last().map(|(_, expected)| *expected);
+
+        let expected = match t.text {
+            _ if t.kind == Kind::String => continue,
+            "if" | "elseif" => "then",
+            "while" => "do",
+            // `for` ... `do`, or `do` ... `end`
+            "for" => "do",
+            "do" if top == Some("do") => {
+                stack.pop();
+                "end"
+            }
+            "then" | "else" => {
+                stack.pop();
+                "end"
+            }
+            "function" | "do" => "end",
+            "repeat" => "until",
+            "(" => ")",
+            "{" => "}",
+            "[" => "]",
+            "end" | "until" | ")" | "}" | "]" => {
+                if top == Some(t.text) {
+                    stack.pop();
+                }
+                continue;
+            }
+            _ => continue,
+        };
+
+        // `elseif` and `else` continue the block opened by `if`.
+        if t.text == "elseif" {
+            stack.pop();
+        }
+        stack.push((t.clone(), expected));
+    }
+
+    stack
+}
+
+/// Describe what the parser expected after the opener.
+fn expectation(opener: &Lexeme, expected: &str) -> String {
+    match (opener.text, expected) {
+        ("if", "then") | ("elseif", "then") => {
+            format!("expected `then` after `{}` condition", opener.text)
+        }
+        ("while", "do") => "expected `do` after `while` condition".into(),
+        ("for", "do") => "expected `do` after `for` clause".into(),
+        _ => format!("expected `{}` to close `{}`", expected, opener.text),
+    }
+}
+
+fn parse_err(script: &Script, token: &Token, additional: Option<&str>) -> Diag {
+    let range = (token.start_position().bytes(), token.end_position().bytes());
+    let eof = matches!(*token.token_type(), TokenType::Eof);
 
+    let tokens = lex(script.script());
+    let open = unclosed(&tokens, range.0);
+    let innermost = open.last();
+
+    let mut msg = if eof {
+        "unexpected end of script".to_string()
+    } else {
+        format!("unexpected token `{}`", token)
+    };
+    if let Some((opener, expected)) = innermost {
+        msg = format!("{}, {}", msg, expectation(opener, expected));
+    }
     let msg = format!("in lua: {} (parse_error)", msg);
 
-    let pd = match spans.get(0).cloned() {
-        Some(span) => PDiagnostic::spanned(span.into(), PLevel::Error, msg),
-        None => PDiagnostic::new(PLevel::Error, msg),
+    // End of script is reported at the last token.
+    let range = if eof {
+        tokens.last().map(|t| t.range).unwrap_or(range)
+    } else {
+        range
     };
-    pd.emit();
-}
 
-fn emit_diag(script: &Script, diags: Vec<CheckerDiagnostic>) {
-    for d in diags {
-        let label = d.diagnostic.primary_label.range;
-        let spans = script.range_to_span((label.0 as usize, label.1 as usize));
-        emit_diag_one(spans.clone(), d);
+    let mut diag = Diag::new(range, Severity::Error, msg);
+    if let Some((opener, _)) = innermost {
+        diag.span_notes.push(SpanNote {
+            range: opener.range,
+            message: format!("`{}` is opened here", opener.text),
+        });
     }
+    diag.notes.extend(additional.map(|a| a.to_string()));
+    diag
 }
 
-fn make_cfg(args: &[String]) -> String {
-    let cfg = include_str!("redis.toml").to_string();
+/// The byte offset of the error, which `TokenizerError` exposes only in its message,
+/// e.g. `unexpected character $ at line 1, column 13`.
+fn error_offset(script: &str, error: &TokenizerError) -> Option<usize> {
+    let message = error.to_string();
+    let (_, position) = message.rsplit_once(" at line ")?;
+    let (line, column) = position.split_once(", column ")?;
+    let line: usize = line.parse().ok()?;
+    let column: usize = column.parse().ok()?;
 
-    let cfg = args.iter().fold(cfg, |cfg, arg| {
-        let new_rule = format!(
-            r#"[{}]
-property = true"#,
-            arg
-        );
+    let start: usize = script
+        .split_inclusive('\n')
+        .take(line.checked_sub(1)?)
+        .map(str::len)
+        .sum();
+    let offset = script[start..]
+        .char_indices()
+        .nth(column.checked_sub(1)?)
+        .map(|(i, _)| start + i)
+        .unwrap_or_else(|| script.len());
+    Some(offset)
+}
+
+fn tokenize_errs(script: &Script, error: &TokenizerError) -> Vec<Diag> {
+    let errors = lexer::errors(script.script());
 
-        format!("{}\n{}", cfg, new_rule)
-    });
+    // The tokenizer of `full_moon` stops at the first error; report all we can find.
+    if !errors.is_empty() {
+        return errors
+            .into_iter()
+            .map(|e| {
+                Diag::from(Lint {
+                    range: e.range,
+                    severity: Severity::Error,
+                    code: "parse_error",
+                    message: e.message,
+                    notes: vec![],
+                })
+            })
+            .collect();
+    }
 
-    cfg
+    let pos = error_offset(script.script(), error).unwrap_or(0);
+    vec![Diag::from(Lint {
+        range: (pos, pos + 1),
+        severity: Severity::Error,
+        code: "parse_error",
+        message: error.to_string(),
+        notes: vec![],
+    })]
+}
+
+/// The standard library of Redis, which is parsed once per compilation.
+fn redis_std() -> &'static StandardLibrary {
+    static STD: OnceLock<StandardLibrary> = OnceLock::new();
+    STD.get_or_init(|| toml::from_str(include_str!("redis.toml")).unwrap())
+}
+
+/// The standard library of Redis plus the globals defined for the script.
+fn make_std(defined: &[String]) -> StandardLibrary {
+    let mut std = redis_std().clone();
+    for name in defined {
+        std.globals
+            .insert(name.clone(), Field::Property { writable: None });
+    }
+    std
 }
 
 pub struct Checker {
@@ -97,36 +413,176 @@ impl Checker {
         self
     }
 
-    pub fn check(&self, script: &Script) {
+    /// Lint the script; errors are emitted and the code raising warnings is returned.
+    pub fn check(&self, script: &Script) -> (Usage, TokenStream) {
+        let args: Vec<_> = script
+            .args()
+            .iter()
+            .map(|a| a.as_lua().to_string())
+            .collect();
+        let key = cache::key(script.script(), &self.defined, &args);
+
+        let entry = cache::load(&key).unwrap_or_else(|| {
+            let entry = self.lint(script, &args);
+            cache::store(&key, &entry);
+            entry
+        });
+
+        let warnings = entry
+            .diags
+            .into_iter()
+            .map(|diag| diag.emit(script))
+            .collect();
+
+        (entry.usage, warnings)
+    }
+
+    fn lint(&self, script: &Script, args: &[String]) -> cache::Entry {
+        let fail = |diags: Vec<Diag>| cache::Entry {
+            usage: Usage::default(),
+            diags,
+        };
+
+        // Reject syntax of newer Lua or Rust before parsing; the parser would
+        // either accept it or report it as a generic unexpected token.
+        let mut unsupported = syntax::check(script.script());
+        unsupported.extend(rustisms(script.script()));
+        unsupported.sort_by_key(|lint| lint.range);
+
+        let fatal = unsupported
+            .iter()
+            .any(|lint| matches!(lint.severity, Severity::Error));
+        let mut diags: Vec<Diag> = unsupported.into_iter().map(Diag::from).collect();
+        if fatal {
+            return fail(diags);
+        }
+
         let ast = match full_moon::parse(script.script()) {
             Ok(ast) => ast.owned(),
-            Err(ParseError::AstError(AstError::UnexpectedToken {
-                token,
-                additional: _,
-            })) => {
-                return emit_parse_err(
-                    script,
-                    &format!("unexpected token `{}`", token),
-                    Some(&token),
-                );
+            Err(ParseError::AstError(AstError::UnexpectedToken { token, additional })) => {
+                diags.push(parse_err(script, &token, additional.as_deref()));
+                return fail(diags);
+            }
+            Err(ParseError::TokenizerError(error)) => {
+                diags.extend(tokenize_errs(script, &error));
+                return fail(diags);
             }
-            Err(_) => {
-                return emit_parse_err(script, "cannot tokenize lua script", None);
+            Err(e) => {
+                diags.push(Diag::from(Lint {
+                    range: (0, script.script().len()),
+                    severity: Severity::Error,
+                    code: "parse_error",
+                    message: e.to_string(),
+                    notes: vec![],
+                }));
+                return fail(diags);
             }
         };
 
-        let std = StandardLibrary::from_file(&as_path(&make_cfg(&self.defined))).unwrap();
+        let std = make_std(&self.defined);
+        let names: HashSet<String> = std.globals.keys().cloned().collect();
         let cfg: CheckerConfig<toml::value::Value> =
             toml::from_str(include_str!("selene.toml")).unwrap();
 
         // Create a linter
-        let checker = SeleneChecker::new(cfg, std.unwrap()).unwrap();
+        let checker = SeleneChecker::new(cfg, std).unwrap();
 
         // Run the linter
-        let mut diags = checker.test_on(&ast);
-        diags.sort_by_key(|d| d.diagnostic.start_position());
+        let mut selene = checker.test_on(&ast);
+        selene.sort_by_key(|d| d.diagnostic.start_position());
+        diags.extend(selene.into_iter().map(Diag::from));
+
+        // Run the lints specific to Redis
+        let (usage, lints) = limits::check(&ast, args);
+
+        let globals = globals::check(&ast, &names, args);
+
+        diags.extend(
+            nil::check(&ast, args)
+                .into_iter()
+                .chain(lints)
+                .chain(globals)
+                .map(Diag::from),
+        );
+
+        cache::Entry { usage, diags }
+    }
+}
 
-        // Emit results as compiler messages
-        emit_diag(script, diags);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The messages of the lints with `true` for errors.
+    fn lints(script: &str) -> Vec<(String, bool)> {
+        rustisms(script)
+            .into_iter()
+            .map(|lint| (lint.message, matches!(lint.severity, Severity::Error)))
+            .collect()
+    }
+
+    #[test]
+    fn rust_operators() {
+        let cases = &[
+            ("return a != b", "`!=` is not a lua operator"),
+            ("return a && b", "`&&` is not a lua operator"),
+            ("return a || b", "`||` is not a lua operator"),
+            ("return !a", "`!` is not a lua operator"),
+            ("x += 1", "compound assignment `+=` is not available in lua"),
+            ("return null", "`null` is not defined in lua"),
+        ];
+        for (script, message) in cases {
+            assert_eq!(
+                lints(script),
+                vec![(message.to_string(), true)],
+                "{}",
+                script
+            );
+        }
+    }
+
+    #[test]
+    fn heuristics() {
+        let cases = &[
+            ("return 'a' + b", "`+` does not concatenate strings in lua"),
+            ("return t[0]", "lua arrays start at index 1, not 0"),
+            (
+                "return t.len()",
+                "`.len()` calls the field `len` in lua, not the length",
+            ),
+        ];
+        for (script, message) in cases {
+            assert_eq!(
+                lints(script),
+                vec![(message.to_string(), false)],
+                "{}",
+                script
+            );
+        }
+    }
+
+    #[test]
+    fn lua_syntax() {
+        let cases = &[
+            "return a ~= b and not c or d",
+            "x = x + 1",
+            "local null = 1 return null",
+            "return cjson.null",
+            "return 'a' .. b, 1 + 2",
+            "return t[1], t[10], {0}",
+            "return #t, t.len",
+            "return 'a != b && c'",
+        ];
+        for script in cases {
+            assert!(lints(script).is_empty(), "{}", script);
+        }
+    }
+
+    #[test]
+    fn tokenizer_error_offset() {
+        for (script, offset) in &[("local a = 1 $", 12), ("local a = 1\nlocal b = $", 22)] {
+            let error = full_moon::tokenizer::tokens(script).unwrap_err();
+            assert_eq!(error_offset(script, &error), Some(*offset), "{}", script);
+        }
     }
 }
@@ -0,0 +1,128 @@
+use proc_macro2::TokenStream;
+use proc_macro_error::{abort, emit_error};
+use quote::{format_ident, quote};
+use syn::{Data, DeriveInput, Fields};
+
+/// Error codes which redis-rs or `redis_lua::Error` classify by themselves,
+/// so a reply tagged by one of them never reaches `ScriptError::decode`.
+const RESERVED_TAGS: &[&str] = &[
+    "ASK",
+    "BUSY",
+    "CLUSTERDOWN",
+    "CROSSSLOT",
+    "ERR",
+    "EXECABORT",
+    "LOADING",
+    "MASTERDOWN",
+    "MOVED",
+    "NOSCRIPT",
+    "OOM",
+    "TRYAGAIN",
+];
+
+/// The tag of the variant in `SCREAMING_SNAKE_CASE`, e.g. `INSUFFICIENT_FUNDS`.
+fn tag(name: &str) -> String {
+    let mut tag = String::new();
+    let mut prev = None::<char>;
+    for c in name.chars() {
+        if c.is_uppercase() && prev.map_or(false, |p| p.is_lowercase() || p.is_ascii_digit()) {
+            tag.push('_');
+        }
+        tag.extend(c.to_uppercase());
+        prev = Some(c);
+    }
+    tag
+}
+
+/// Implement `ScriptError` for the enum.
+pub fn script_error(input: DeriveInput) -> TokenStream {
+    let name = &input.ident;
+
+    let data = match &input.data {
+        Data::Enum(data) => data,
+        _ => abort!(name, "`ScriptError` can be derived only for enums"),
+    };
+    if !input.generics.params.is_empty() {
+        abort!(
+            input.generics,
+            "`ScriptError` can't be derived for generic enums"
+        );
+    }
+
+    for v in &data.variants {
+        let tag = tag(&v.ident.to_string());
+        if RESERVED_TAGS.contains(&tag.as_str()) {
+            emit_error!(
+                v.ident,
+                "the tag `{}` is an error code of Redis, which is never decoded as `{}`", tag, name;
+                help = "rename the variant"
+            );
+        }
+    }
+
+    let decoders = data.variants.iter().map(|v| {
+        let ident = &v.ident;
+        let tag = tag(&ident.to_string());
+
+        match &v.fields {
+            Fields::Unit => quote! {
+                #tag => Some(#name::#ident),
+            },
+            Fields::Unnamed(fields) => {
+                let vars: Vec<_> = (0..fields.unnamed.len())
+                    .map(|i| format_ident!("f{}", i))
+                    .collect();
+                let types = fields.unnamed.iter().map(|f| &f.ty);
+                quote! {
+                    #tag => {
+                        let (#(#vars,)*): (#(#types,)*) = redis_lua::decode_fields(fields)?;
+                        Some(#name::#ident(#(#vars),*))
+                    }
+                }
+            }
+            Fields::Named(fields) => {
+                let names: Vec<_> = fields.named.iter().map(|f| &f.ident).collect();
+                let types = fields.named.iter().map(|f| &f.ty);
+                quote! {
+                    #tag => {
+                        #[derive(redis_lua::serde::Deserialize)]
+                        #[serde(crate = "redis_lua::serde")]
+                        struct Fields {
+                            #(#names: #types),*
+                        }
+                        let f: Fields = redis_lua::decode_fields(fields)?;
+                        Some(#name::#ident { #(#names: f.#names),* })
+                    }
+                }
+            }
+        }
+    });
+
+    let tags = data.variants.iter().map(|v| {
+        let ident = &v.ident;
+        let tag = tag(&ident.to_string());
+        let pattern = match &v.fields {
+            Fields::Unit => quote! { #name::#ident },
+            Fields::Unnamed(_) => quote! { #name::#ident(..) },
+            Fields::Named(_) => quote! { #name::#ident { .. } },
+        };
+        quote! { #pattern => #tag, }
+    });
+
+    quote! {
+        impl redis_lua::ScriptError for #name {
+            fn decode(tag: &str, fields: &[u8]) -> Option<Self> {
+                match tag {
+                    #(#decoders)*
+                    _ => None,
+                }
+            }
+
+            fn tag(&self) -> &'static str {
+                match self {
+                    #(#tags)*
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,156 @@
+//! Generation of the complete script at compile time.
+//!
+//! The text must match the one generated by `redis_lua::gen_script` for a script which is not joined.
+
+use crate::{
+    limits::{Usage, MAX_LOCALS, MAX_UPVALUES},
+    script::ARG_TABLE,
+};
+
+/// Helpers at the top of every script, which are shared with `redis_lua::gen_script`.
+///
+/// The files in `prelude` must be single lines without a trailing newline to keep the line numbers.
+#[cfg(not(feature = "minify"))]
+const PRELUDE: &str = concat!(
+    include_str!("prelude/script_error.lua"),
+    " ",
+    include_str!("prelude/args.lua"),
+    " "
+);
+
+#[cfg(feature = "minify")]
+const PRELUDE: &str = concat!(
+    include_str!("prelude/script_error.min.lua"),
+    " ",
+    include_str!("prelude/args.min.lua"),
+    " "
+);
+
+/// Returns `true` if the arguments don't fit in local variables.
+fn table_layout(usage: &Usage, args: &[String]) -> bool {
+    usage.locals + args.len() > MAX_LOCALS || usage.upvalues > MAX_UPVALUES
+}
+
+#[cfg(not(feature = "minify"))]
+fn code(body: &str, table: &str, args: &[String], usage: &Usage) -> String {
+    let values = (1..=args.len()).map(|i| format!("__internal_arg({})", i));
+
+    let (init, body) = if table_layout(usage, args) {
+        let table_init: Vec<_> = values.map(|v| format!("({})", v)).collect();
+        let init = format!("local {} = {{{}}} ", ARG_TABLE, table_init.join(", "));
+        (init, table)
+    } else {
+        let init = args
+            .iter()
+            .zip(values)
+            .map(|(arg, value)| format!("local {} = {} ", arg, value))
+            .collect();
+        (init, body)
+    };
+
+    format!("{}return (function() {} {} end)();\n", PRELUDE, init, body)
+}
+
+#[cfg(feature = "minify")]
+fn code(body: &str, table: &str, args: &[String], usage: &Usage) -> String {
+    let values: Vec<_> = (1..=args.len()).map(|i| format!("__g({})", i)).collect();
+
+    let (init, body) = if args.is_empty() {
+        (String::new(), body)
+    } else if table_layout(usage, args) {
+        let table_init: Vec<_> = values.iter().map(|v| format!("({})", v)).collect();
+        (
+            format!("local {}={{{}}} ", ARG_TABLE, table_init.join(",")),
+            table,
+        )
+    } else {
+        let init = format!("local {}={} ", args.join(","), values.join(","));
+        (init, body)
+    };
+
+    format!("{}return(function(){}{} end)()\n", PRELUDE, init, body)
+}
+
+/// The complete script and its SHA1 hash.
+pub fn script(body: &str, table: &str, args: &[String], usage: &Usage) -> (String, String) {
+    let code = code(body, table, args, usage);
+    let hash = sha1::Sha1::from(&code).digest().to_string();
+    (code, hash)
+}
+
+/// Helpers at the top of a function library.
+///
+/// Functions can't refer to `ARGV`, so `__internal_unpack(args)` returns the function
+/// which unpacks the argument as `__internal_arg(i)` does in scripts.
+const LIBRARY_PRELUDE: &str = concat!(
+    include_str!("prelude/script_error.lua"),
+    " ",
+    include_str!("prelude/unpack.lua"),
+    "\n"
+);
+
+/// A function in a library.
+pub struct Function {
+    pub name: String,
+    pub body: String,
+    pub table: String,
+    /// The local variables of the arguments and their positions in the parameters, starting from 1.
+    pub args: Vec<(String, usize)>,
+    pub usage: Usage,
+    pub no_writes: bool,
+}
+
+impl Function {
+    fn register(&self) -> String {
+        let names: Vec<_> = self.args.iter().map(|(name, _)| name.clone()).collect();
+        let values = self
+            .args
+            .iter()
+            .map(|(_, i)| format!("__internal_arg({})", i));
+
+        let (init, body) = if table_layout(&self.usage, &names) {
+            let table_init: Vec<_> = values.map(|v| format!("({})", v)).collect();
+            let init = format!("local {} = {{{}}} ", ARG_TABLE, table_init.join(", "));
+            (init, self.table.as_str())
+        } else {
+            let init = names
+                .iter()
+                .zip(values)
+                .map(|(arg, value)| format!("local {} = {} ", arg, value))
+                .collect();
+            (init, self.body.as_str())
+        };
+
+        let flags = if self.no_writes {
+            ", flags={'no-writes'}"
+        } else {
+            ""
+        };
+
+        format!(
+            "redis.register_function{{function_name='{}', callback=function(keys, args) \
+             local __internal_arg = __internal_unpack(args) {} {}\nend{}}}\n",
+            self.name, init, body, flags
+        )
+    }
+}
+
+/// The source of the function library loaded by `FUNCTION LOAD`.
+pub fn library(name: &str, functions: &[Function]) -> String {
+    let mut code = format!("#!lua name={}\n{}", name, LIBRARY_PRELUDE);
+    for function in functions {
+        code += &function.register();
+    }
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_line_prelude() {
+        assert!(!PRELUDE.contains('\n'));
+        assert_eq!(LIBRARY_PRELUDE.matches('\n').count(), 1);
+    }
+}
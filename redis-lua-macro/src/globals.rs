@@ -0,0 +1,310 @@
+use crate::{
+    ast::{global_field, ident, range, string, value, var_name},
+    check::Lint,
+};
+use full_moon::{
+    ast::{
+        Assignment, Ast, Block, Call, FunctionArgs, FunctionBody, FunctionCall,
+        FunctionDeclaration, GenericFor, LocalAssignment, LocalFunction, NumericFor, Parameter,
+        Prefix, Suffix, Value, Var,
+    },
+    visitors::Visitor,
+};
+use selene_lib::rules::Severity;
+use std::collections::HashSet;
+
+/// Detects the access to global variables which Redis rejects at runtime.
+///
+/// Redis raises an error when a script creates a global variable,
+/// and since 7.0 also when it reads a global variable which doesn't exist.
+struct Globals<'a> {
+    std: &'a HashSet<String>,
+    scopes: Vec<Vec<String>>,
+    /// Assignment targets, which are not reads.
+    targets: HashSet<(usize, usize)>,
+    lints: Vec<Lint>,
+}
+
+impl<'a> Globals<'a> {
+    fn new(std: &'a HashSet<String>, args: &[String]) -> Self {
+        Self {
+            std,
+            // Arguments are local variables of the generated script.
+            scopes: vec![args.to_vec()],
+            targets: HashSet::new(),
+            lints: Vec::new(),
+        }
+    }
+
+    fn declare(&mut self, name: String) {
+        self.scopes.last_mut().unwrap().push(name);
+    }
+
+    fn is_local(&self, name: &str) -> bool {
+        self.scopes.iter().flatten().any(|n| n == name)
+    }
+
+    fn create(&mut self, range: (usize, usize), name: &str, fix: String) {
+        let message = if self.std.contains(name) {
+            format!("Script attempted to modify global variable `{}`", name)
+        } else {
+            format!("Script attempted to create global variable `{}`", name)
+        };
+        self.lints.push(Lint {
+            range,
+            severity: Severity::Error,
+            code: "global_variable",
+            message,
+            notes: vec![fix],
+        });
+    }
+
+    fn read(&mut self, range: (usize, usize), field: Option<String>) {
+        let message = match field {
+            Some(name) if self.std.contains(&name) => return,
+            Some(name) => format!(
+                "Script attempted to access nonexistent global variable `{}`",
+                name
+            ),
+            None => "Script attempted to access a global variable by a dynamic name".into(),
+        };
+        self.lints.push(Lint {
+            range,
+            severity: Severity::Error,
+            code: "global_variable",
+            message,
+            notes: vec!["Redis 7 rejects the access to nonexistent global variables".into()],
+        });
+    }
+}
+
+impl<'a, 'ast> Visitor<'ast> for Globals<'a> {
+    fn visit_block(&mut self, _: &Block<'ast>) {
+        self.scopes.push(vec![]);
+    }
+
+    fn visit_block_end(&mut self, _: &Block<'ast>) {
+        self.scopes.pop();
+    }
+
+    fn visit_function_body(&mut self, body: &FunctionBody<'ast>) {
+        self.scopes.push(vec![]);
+        for param in body.iter_parameters() {
+            if let Parameter::Name(name) = param {
+                self.declare(ident(name).unwrap_or_default());
+            }
+        }
+    }
+
+    fn visit_function_body_end(&mut self, _: &FunctionBody<'ast>) {
+        self.scopes.pop();
+    }
+
+    fn visit_local_assignment_end(&mut self, assign: &LocalAssignment<'ast>) {
+        for name in assign.name_list().iter() {
+            self.declare(ident(name).unwrap_or_default());
+        }
+    }
+
+    fn visit_local_function(&mut self, func: &LocalFunction<'ast>) {
+        self.declare(ident(func.name()).unwrap_or_default());
+    }
+
+    fn visit_numeric_for(&mut self, stmt: &NumericFor<'ast>) {
+        self.scopes.push(vec![]);
+        self.declare(ident(stmt.index_variable()).unwrap_or_default());
+    }
+
+    fn visit_numeric_for_end(&mut self, _: &NumericFor<'ast>) {
+        self.scopes.pop();
+    }
+
+    fn visit_generic_for(&mut self, stmt: &GenericFor<'ast>) {
+        self.scopes.push(vec![]);
+        for name in stmt.names().iter() {
+            self.declare(ident(name).unwrap_or_default());
+        }
+    }
+
+    fn visit_generic_for_end(&mut self, _: &GenericFor<'ast>) {
+        self.scopes.pop();
+    }
+
+    fn visit_function_declaration(&mut self, decl: &FunctionDeclaration<'ast>) {
+        let names: Vec<_> = decl.name().names().iter().collect();
+        let first = names.first().and_then(|n| ident(n)).unwrap_or_default();
+
+        if first == "_G" && names.len() == 2 {
+            let name = ident(names[1]).unwrap_or_default();
+            let fix = format!("try `local function {}`", name);
+            self.create(range(decl.name()), &name, fix);
+        } else if names.len() == 1 && decl.name().method_name().is_none() && !self.is_local(&first)
+        {
+            let fix = format!("try `local function {}`", first);
+            self.create(range(decl.name()), &first, fix);
+        }
+    }
+
+    fn visit_assignment(&mut self, assign: &Assignment<'ast>) {
+        for var in assign.var_list().iter() {
+            self.targets.insert(range(var));
+
+            match var {
+                Var::Name(name) => {
+                    let name = ident(name).unwrap_or_default();
+                    if !self.is_local(&name) {
+                        let fix = format!("try `local {} = ...`", name);
+                        self.create(range(var), &name, fix);
+                    }
+                }
+                Var::Expression(expr) => {
+                    if let Some(field) = global_field(expr.prefix(), expr.iter_suffixes()) {
+                        let name = field.unwrap_or_else(|| "?".into());
+                        let fix = format!("try `local {} = ...`", name);
+                        self.create(range(var), &name, fix);
+                    }
+                }
+            }
+        }
+    }
+
+    fn visit_var(&mut self, var: &Var<'ast>) {
+        if self.targets.contains(&range(var)) {
+            return;
+        }
+        if let Var::Expression(expr) = var {
+            if let Some(field) = global_field(expr.prefix(), expr.iter_suffixes()) {
+                self.read(range(var), field);
+            }
+        }
+    }
+
+    fn visit_function_call(&mut self, call: &FunctionCall<'ast>) {
+        if let Some(field) = global_field(call.prefix(), call.iter_suffixes()) {
+            self.read(range(call), field);
+        }
+
+        // `rawset(_G, "x", 1)` and `setmetatable(_G, ...)`
+        let func = match call.prefix() {
+            Prefix::Name(name) => ident(name).unwrap_or_default(),
+            _ => return,
+        };
+        if func != "rawset" && func != "setmetatable" {
+            return;
+        }
+        let args = match call.iter_suffixes().next() {
+            Some(Suffix::Call(Call::AnonymousCall(FunctionArgs::Parentheses {
+                arguments,
+                ..
+            }))) => arguments,
+            _ => return,
+        };
+        let target = args.iter().next();
+        if target.and_then(|e| var_name(e)).as_deref() != Some("_G") {
+            return;
+        }
+
+        if func == "rawset" {
+            let name = match args.iter().nth(1).and_then(|e| value(e)) {
+                Some(Value::String(token)) => string(token).unwrap_or_else(|| "?".into()),
+                _ => "?".into(),
+            };
+            let fix = "try a local variable".to_string();
+            self.create(range(call), &name, fix);
+        } else {
+            self.lints.push(Lint {
+                range: range(call),
+                severity: Severity::Error,
+                code: "global_variable",
+                message: "Script attempted to modify the metatable of `_G`".into(),
+                notes: vec!["Redis protects the global table with its own metatable".into()],
+            });
+        }
+    }
+}
+
+/// Check the access to global variables.
+///
+/// `std` are the names of the globals which Redis provides.
+pub fn check(ast: &Ast, std: &HashSet<String>, args: &[String]) -> Vec<Lint> {
+    let mut globals = Globals::new(std, args);
+    globals.visit_ast(ast);
+    globals.lints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(code: &str, args: &[&str]) -> Vec<String> {
+        let ast = full_moon::parse(code).unwrap();
+        let std = ["redis", "string", "KEYS", "ARGV"]
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+        let args: Vec<_> = args.iter().map(|arg| arg.to_string()).collect();
+        check(&ast, &std, &args)
+            .into_iter()
+            .map(|lint| lint.message)
+            .collect()
+    }
+
+    #[test]
+    fn create() {
+        let cases = &[
+            ("x = 1", "create global variable `x`"),
+            ("function f() end", "create global variable `f`"),
+            ("function _G.f() end", "create global variable `f`"),
+            ("_G.x = 1", "create global variable `x`"),
+            ("_G['x'] = 1", "create global variable `x`"),
+            ("rawset(_G, 'x', 1)", "create global variable `x`"),
+            ("string = nil", "modify global variable `string`"),
+            ("setmetatable(_G, {})", "modify the metatable of `_G`"),
+        ];
+        for (code, message) in cases {
+            assert_eq!(
+                messages(code, &[]),
+                vec![format!("Script attempted to {}", message)],
+                "{}",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn read() {
+        assert_eq!(
+            messages("return _G.missing", &[]),
+            vec!["Script attempted to access nonexistent global variable `missing`"]
+        );
+        assert_eq!(
+            messages("local k = 'a' return _G[k]", &[]),
+            vec!["Script attempted to access a global variable by a dynamic name"]
+        );
+        assert!(messages("return _G.redis", &[]).is_empty());
+    }
+
+    #[test]
+    fn locals() {
+        let cases = &[
+            "local x = 1 x = 2",
+            "local function f() end f()",
+            "local function f(a) a = 1 end",
+            "for i = 1, 2 do i = 3 end",
+            "for k, v in pairs(KEYS) do k, v = v, k end",
+            "local t = {} t.x = 1 function t.f() end function t:g() end",
+            "__a1 = 2",
+        ];
+        for code in cases {
+            assert!(messages(code, &["__a1"]).is_empty(), "{}", code);
+        }
+    }
+
+    #[test]
+    fn scopes() {
+        assert_eq!(
+            messages("do local x = 1 end x = 2", &[]),
+            vec!["Script attempted to create global variable `x`"]
+        );
+    }
+}
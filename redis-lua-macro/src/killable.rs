@@ -0,0 +1,144 @@
+//! Whether a script can be killed by `SCRIPT KILL`.
+//!
+//! Redis refuses to kill a script once it has written, so a script is regarded as killable
+//! only if all the commands it calls are read-only. Commands given by variables may write.
+
+use crate::lexer::{lex, Kind, Lexeme};
+
+/// Read-only commands.
+const READ_ONLY: &[&str] = &[
+    "BITCOUNT",
+    "BITPOS",
+    "DBSIZE",
+    "DUMP",
+    "ECHO",
+    "EXISTS",
+    "GEODIST",
+    "GEOHASH",
+    "GEOPOS",
+    "GEORADIUSBYMEMBER_RO",
+    "GEORADIUS_RO",
+    "GEOSEARCH",
+    "GET",
+    "GETBIT",
+    "GETRANGE",
+    "HEXISTS",
+    "HGET",
+    "HGETALL",
+    "HKEYS",
+    "HLEN",
+    "HMGET",
+    "HRANDFIELD",
+    "HSCAN",
+    "HSTRLEN",
+    "HVALS",
+    "KEYS",
+    "LINDEX",
+    "LLEN",
+    "LPOS",
+    "LRANGE",
+    "MGET",
+    "OBJECT",
+    "PFCOUNT",
+    "PING",
+    "PTTL",
+    "RANDOMKEY",
+    "SCAN",
+    "SCARD",
+    "SDIFF",
+    "SINTER",
+    "SINTERCARD",
+    "SISMEMBER",
+    "SMEMBERS",
+    "SMISMEMBER",
+    "SRANDMEMBER",
+    "SSCAN",
+    "STRLEN",
+    "SUBSTR",
+    "SUNION",
+    "TIME",
+    "TTL",
+    "TYPE",
+    "XINFO",
+    "XLEN",
+    "XPENDING",
+    "XRANGE",
+    "XREAD",
+    "XREVRANGE",
+    "ZCARD",
+    "ZCOUNT",
+    "ZDIFF",
+    "ZINTER",
+    "ZLEXCOUNT",
+    "ZMSCORE",
+    "ZRANDMEMBER",
+    "ZRANGE",
+    "ZRANGEBYLEX",
+    "ZRANGEBYSCORE",
+    "ZRANK",
+    "ZREVRANGE",
+    "ZREVRANGEBYLEX",
+    "ZREVRANGEBYSCORE",
+    "ZREVRANK",
+    "ZSCAN",
+    "ZSCORE",
+    "ZUNION",
+];
+
+/// The command name if the token is a string literal, e.g. `"GET"`.
+fn command<'a>(token: &Lexeme<'a>) -> Option<&'a str> {
+    if token.kind != Kind::String {
+        return None;
+    }
+    let text = token.text;
+    let quote = text.chars().next()?;
+    text.strip_prefix(quote)?.strip_suffix(quote)
+}
+
+fn read_only(token: Option<&Lexeme>) -> bool {
+    token.and_then(command).map_or(false, |c| {
+        READ_ONLY.contains(&c.to_ascii_uppercase().as_str())
+    })
+}
+
+/// Returns `true` if the script calls only read-only commands.
+pub fn killable(script: &str) -> bool {
+    let tokens = lex(script);
+
+    (0..tokens.len()).all(|i| {
+        let is = |offset: usize, s: &str| tokens.get(i + offset).map_or(false, |t| t.is(s));
+
+        if !(is(0, "redis") && is(1, ".") && (is(2, "call") || is(2, "pcall"))) {
+            return true;
+        }
+
+        // `redis.call("GET", ...)` or `redis.call "GET"`
+        if is(3, "(") {
+            read_only(tokens.get(i + 4))
+        } else {
+            read_only(tokens.get(i + 3))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_only_commands() {
+        assert!(killable("return 1"));
+        assert!(killable(r#"return redis.call("GET", KEYS[1])"#));
+        assert!(killable(r#"return redis.pcall('hgetall', KEYS[1])"#));
+        assert!(killable(r#"return redis.call "ping""#));
+    }
+
+    #[test]
+    fn writing_commands() {
+        assert!(!killable(
+            r#"redis.call("GET", "a") return redis.call("SET", "a", 1)"#
+        ));
+        assert!(!killable(r#"return redis.pcall("DEL", "a")"#));
+        assert!(!killable("local cmd = 'GET' return redis.call(cmd, 'a')"));
+    }
+}
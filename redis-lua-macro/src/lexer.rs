@@ -0,0 +1,180 @@
+/// Kind of lua token.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Name,
+    Number,
+    String,
+    Symbol,
+}
+
+/// Lua token with the byte range in the script.
+///
+/// Unlike `full_moon`'s tokenizer, this accepts any symbol (e.g. `&&` or `!=`)
+/// so that the syntax not available in Lua 5.1 can be reported with a specific message.
+#[derive(Clone, Debug)]
+pub struct Lexeme<'a> {
+    pub kind: Kind,
+    pub text: &'a str,
+    pub range: (usize, usize),
+}
+
+impl<'a> Lexeme<'a> {
+    pub fn is(&self, s: &str) -> bool {
+        self.kind != Kind::String && self.text == s
+    }
+}
+
+/// Symbols made of multiple characters; the longest ones first.
+const SYMBOLS: &[&str] = &[
+    "...", "..", "==", "~=", "<=", ">=", "<<", ">>", "::", "!=", "&&", "||", "+=", "-=", "*=",
+    "/=", "%=",
+];
+
+/// Symbols available in Lua 5.1.
+const LUA_SYMBOLS: &[&str] = &[
+    "...", "..", "==", "~=", "<=", ">=", "+", "-", "*", "/", "%", "^", "#", "<", ">", "=", "(",
+    ")", "{", "}", "[", "]", ";", ":", ",", ".",
+];
+
+/// Lexical error such as an unfinished string.
+#[derive(Clone, Debug)]
+pub struct LexError {
+    pub message: String,
+    pub range: (usize, usize),
+}
+
+/// The level of long brackets, e.g. `2` for `[==[`
+fn long_bracket(s: &str) -> Option<usize> {
+    let rest = s.strip_prefix('[')?;
+    let level = rest.chars().take_while(|c| *c == '=').count();
+    if rest[level..].starts_with('[') {
+        Some(level)
+    } else {
+        None
+    }
+}
+
+/// The length of the long bracket and whether it's closed.
+fn skip_long_bracket(s: &str, level: usize) -> (usize, bool) {
+    let close = format!("]{}]", "=".repeat(level));
+    s.find(&close)
+        .map(|i| (i + close.len(), true))
+        .unwrap_or_else(|| (s.len(), false))
+}
+
+/// The length of the quoted string and whether it's closed.
+fn skip_quoted(s: &str, quote: char) -> (usize, bool) {
+    let mut escaped = false;
+    for (i, c) in s.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '\n' => return (i, false),
+            c if c == quote => return (i + 1, true),
+            _ => {}
+        }
+    }
+    (s.len(), false)
+}
+
+fn skip_number(s: &str) -> usize {
+    let hex = s.starts_with("0x") || s.starts_with("0X");
+    let mut prev = ' ';
+    for (i, c) in s.char_indices() {
+        let exp = if hex {
+            prev == 'p' || prev == 'P'
+        } else {
+            prev == 'e' || prev == 'E'
+        };
+        if !(c.is_ascii_alphanumeric() || c == '.' || c == '_' || ((c == '+' || c == '-') && exp)) {
+            return i;
+        }
+        prev = c;
+    }
+    s.len()
+}
+
+/// Split the lua script into tokens skipping whitespaces and comments.
+pub fn lex(script: &str) -> Vec<Lexeme<'_>> {
+    scan(script).0
+}
+
+/// Find all the lexical errors of the script.
+pub fn errors(script: &str) -> Vec<LexError> {
+    scan(script).1
+}
+
+fn scan(script: &str) -> (Vec<Lexeme<'_>>, Vec<LexError>) {
+    let mut tokens = Vec::new();
+    let mut errors = Vec::new();
+    let mut pos = 0;
+
+    let mut error = |len: usize, pos: usize, message: &str| {
+        errors.push(LexError {
+            message: message.into(),
+            range: (pos, pos + len),
+        })
+    };
+
+    while pos < script.len() {
+        let rest = &script[pos..];
+        let c = rest.chars().next().unwrap();
+
+        let (kind, len) = if c.is_whitespace() {
+            pos += c.len_utf8();
+            continue;
+        } else if rest.starts_with("--") {
+            pos += match long_bracket(&rest[2..]) {
+                Some(level) => {
+                    let (len, closed) = skip_long_bracket(&rest[2..], level);
+                    if !closed {
+                        error(2 + level + 2, pos, "unfinished long comment");
+                    }
+                    2 + len
+                }
+                None => rest.find('\n').unwrap_or_else(|| rest.len()),
+            };
+            continue;
+        } else if let Some(level) = long_bracket(rest) {
+            let (len, closed) = skip_long_bracket(rest, level);
+            if !closed {
+                error(level + 2, pos, "unfinished long string");
+            }
+            (Kind::String, len)
+        } else if c == '"' || c == '\'' {
+            let (len, closed) = skip_quoted(rest, c);
+            if !closed {
+                error(len, pos, "unfinished string");
+            }
+            (Kind::String, len)
+        } else if c.is_ascii_digit()
+            || (c == '.' && rest[1..].starts_with(|c: char| c.is_ascii_digit()))
+        {
+            (Kind::Number, skip_number(rest))
+        } else if c.is_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or_else(|| rest.len());
+            (Kind::Name, len)
+        } else {
+            let len = SYMBOLS
+                .iter()
+                .find(|s| rest.starts_with(*s))
+                .map(|s| s.len())
+                .unwrap_or_else(|| c.len_utf8());
+            if !LUA_SYMBOLS.contains(&&rest[..len]) {
+                error(len, pos, &format!("unexpected symbol `{}`", &rest[..len]));
+            }
+            (Kind::Symbol, len)
+        };
+
+        tokens.push(Lexeme {
+            kind,
+            text: &rest[..len],
+            range: (pos, pos + len),
+        });
+        pos += len;
+    }
+
+    (tokens, errors)
+}
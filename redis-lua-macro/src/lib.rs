@@ -5,18 +5,31 @@ use proc_macro2::TokenStream;
 use proc_macro_error::proc_macro_error;
 use quote::quote;
 
+mod ast;
+mod cache;
 mod chains;
 mod check;
-mod file;
+mod derive;
+mod gen;
+mod globals;
+mod killable;
+mod lexer;
+mod library;
+mod limits;
+mod literal;
+#[cfg(feature = "minify")]
+mod minify;
+mod nil;
 mod patterns;
 mod script;
+mod syntax;
 mod token;
 
 use crate::{
     chains::ChainIter,
     check::Checker,
     patterns::{all, caps},
-    script::Script,
+    script::{Script, ARG_TABLE},
 };
 
 use proc_macro_hack::proc_macro_hack;
@@ -26,6 +39,15 @@ fn to_ident(tt: &TokenTree) -> TokenStream {
     s.into()
 }
 
+/// The script text to be emitted, which is minified with `minify` feature.
+fn emit(script: &str) -> String {
+    #[cfg(feature = "minify")]
+    return minify::minify(script);
+
+    #[cfg(not(feature = "minify"))]
+    return script.to_string();
+}
+
 fn gen_all(script: &Script) -> TokenStream {
     let mut s = TokenStream::new();
 
@@ -48,14 +70,25 @@ fn gen_all(script: &Script) -> TokenStream {
 pub fn lua(input: TokenStream1) -> TokenStream1 {
     let script = Script::new(input, true);
 
-    Checker::new()
+    let (usage, warnings) = Checker::new()
         .defines(all(&script).map(|(_, arg)| arg.as_lua().into()).collect())
         .check(&script);
 
     let defs = gen_all(&script);
 
-    let body_str = script.script();
-    let script_str = script.wrap();
+    let body_str = emit(script.script());
+    let table_str = emit(script.table());
+    let arg_table = ARG_TABLE;
+    let locals = usage.locals;
+    let upvalues = usage.upvalues;
+    let (file, line, column) = script.location();
+    let location = format!(" ({}:{}:{})", file, line, column);
+
+    let arg_names: Vec<_> = all(&script)
+        .map(|(_, arg)| arg.as_lua().to_string())
+        .collect();
+    let (code, hash) = gen::script(&body_str, &table_str, &arg_names, &usage);
+    let killable = killable::killable(script.script());
 
     let args = all(&script).map(|(_, arg)| {
         let arg = arg.as_lua().to_string();
@@ -71,9 +104,34 @@ pub fn lua(input: TokenStream1) -> TokenStream1 {
         {
             use redis_lua::Script;
 
+            #warnings
+
             #defs
 
-            Chain0::new(redis_lua::Info::new(#script_str, #body_str, &[#(#args),*]), (), #(#caps),*)
+            redis_lua::inventory::submit! {
+                redis_lua::Registered::new(
+                    concat!(module_path!(), #location),
+                    redis_lua::Location::new(#file, #line, #column),
+                    #code,
+                    #hash,
+                    #killable,
+                )
+            }
+
+            let info = redis_lua::Info::new(
+                #body_str,
+                #table_str,
+                #arg_table,
+                &[#(#args),*],
+                #locals,
+                #upvalues,
+                redis_lua::Location::new(#file, #line, #column),
+                #code,
+                #hash,
+                #killable,
+            );
+
+            Chain0::new(info, (), #(#caps),*)
         }
     };
     script_code.into()
@@ -84,11 +142,34 @@ pub fn lua(input: TokenStream1) -> TokenStream1 {
 pub fn lua_s(input: TokenStream1) -> TokenStream1 {
     let script = Script::new(input, false);
 
-    Checker::new().define("ARGV").check(&script);
+    let (_, warnings) = Checker::new().define("ARGV").check(&script);
 
-    let script = script.script();
-    let script_code = quote! {
-        #script
+    let script = emit(script.script());
+    // Keep a plain literal unless there are warnings, so that it can be used as such.
+    let script_code = if warnings.is_empty() {
+        quote! { #script }
+    } else {
+        quote! {
+            {
+                #warnings
+
+                #script
+            }
+        }
     };
     script_code.into()
 }
+
+#[proc_macro_error]
+#[proc_macro_derive(ScriptError)]
+pub fn script_error(input: TokenStream1) -> TokenStream1 {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+    derive::script_error(input).into()
+}
+
+#[proc_macro_error]
+#[proc_macro]
+pub fn lua_library(input: TokenStream1) -> TokenStream1 {
+    let lib = syn::parse_macro_input!(input as library::Library);
+    library::library(lib).into()
+}
@@ -0,0 +1,183 @@
+use crate::{
+    check::Checker,
+    emit,
+    gen::{self, Function},
+    script::{ArgType, Script},
+};
+use proc_macro2::{Ident, TokenStream};
+use proc_macro_error::{abort, emit_error, Diagnostic, Level};
+use quote::quote;
+use syn::{
+    braced, parenthesized,
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    Attribute, LitStr, Token,
+};
+
+/// Function definition, e.g. `#[no_writes] fn get(key) { return redis.call("GET", $key) }`.
+struct FunctionDef {
+    attrs: Vec<Attribute>,
+    name: Ident,
+    params: Vec<Ident>,
+    body: TokenStream,
+}
+
+impl Parse for FunctionDef {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        input.parse::<Token![fn]>()?;
+        let name = input.parse()?;
+
+        let params;
+        parenthesized!(params in input);
+        let params: Punctuated<Ident, Token![,]> = params.parse_terminated(Ident::parse)?;
+
+        let body;
+        braced!(body in input);
+        let body = body.parse()?;
+
+        Ok(Self {
+            attrs,
+            name,
+            params: params.into_iter().collect(),
+            body,
+        })
+    }
+}
+
+/// Library definition, e.g. `name = "billing"; fn charge(account, amount) { ... }`.
+pub struct Library {
+    name: LitStr,
+    functions: Vec<FunctionDef>,
+}
+
+impl Parse for Library {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let key: Ident = input.parse()?;
+        if key != "name" {
+            return Err(syn::Error::new(key.span(), "expected `name = \"...\";`"));
+        }
+        input.parse::<Token![=]>()?;
+        let name = input.parse()?;
+        input.parse::<Token![;]>()?;
+
+        let mut functions = Vec::new();
+        while !input.is_empty() {
+            functions.push(input.parse()?);
+        }
+
+        Ok(Self { name, functions })
+    }
+}
+
+/// Returns `true` if the function is declared read-only by `#[no_writes]`.
+fn no_writes(def: &FunctionDef) -> bool {
+    let mut no_writes = false;
+    for attr in &def.attrs {
+        if attr.path.is_ident("no_writes") && attr.tokens.is_empty() {
+            no_writes = true;
+        } else {
+            emit_error!(attr, "unknown attribute; only `#[no_writes]` is supported");
+        }
+    }
+    no_writes
+}
+
+/// The positions of the `$` variables in the parameters, starting from 1.
+fn positions(def: &FunctionDef, script: &Script) -> Vec<(String, usize)> {
+    script
+        .args()
+        .iter()
+        .filter_map(|arg| {
+            let name = arg.as_rust().to_string();
+
+            if arg.atype() == ArgType::Cap {
+                let msg = format!("`@{}` can't be captured in a function library", name);
+                let note = format!("use `${}` and add it to the parameters", name);
+                Diagnostic::spanned(arg.key().span().into(), Level::Error, msg)
+                    .note(note)
+                    .emit();
+                return None;
+            }
+
+            match def.params.iter().position(|p| *p == name) {
+                Some(i) => Some((arg.as_lua().to_string(), i + 1)),
+                None => {
+                    let msg = format!("`${}` is not a parameter of `{}`", name, def.name);
+                    Diagnostic::spanned(arg.key().span().into(), Level::Error, msg).emit();
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+pub fn library(lib: Library) -> TokenStream {
+    let name = lib.name.value();
+    let module = match syn::parse_str::<Ident>(&name) {
+        Ok(_) => Ident::new(&name, lib.name.span()),
+        Err(_) => abort!(lib.name, "the library name must be an identifier"),
+    };
+
+    let scripts: Vec<_> = lib
+        .functions
+        .iter()
+        .map(|def| Script::new(def.body.clone().into(), true))
+        .collect();
+
+    let mut functions = Vec::new();
+    let mut callers = Vec::new();
+
+    for (def, script) in lib.functions.iter().zip(&scripts) {
+        let args = positions(def, script);
+        // `keys` is the first parameter of the callback, given by `FunctionCall::route`.
+        let (usage, warnings) = Checker::new()
+            .define("keys")
+            .defines(
+                script
+                    .args()
+                    .iter()
+                    .map(|arg| arg.as_lua().into())
+                    .collect(),
+            )
+            .check(script);
+
+        functions.push(Function {
+            name: def.name.to_string(),
+            body: emit(script.script()),
+            table: emit(script.table()),
+            args,
+            usage,
+            no_writes: no_writes(def),
+        });
+
+        let fname = &def.name;
+        let fname_str = def.name.to_string();
+        let params = &def.params;
+
+        callers.push(quote! {
+            /// Call the function.
+            pub fn #fname(
+                #(#params: impl redis_lua::serde::Serialize),*
+            ) -> redis_lua::FunctionCall {
+                #warnings
+
+                redis_lua::FunctionCall::new(
+                    #fname_str,
+                    vec![#(redis_lua::script_arg(&#params)),*],
+                )
+            }
+        });
+    }
+
+    let code = gen::library(&name, &functions);
+
+    quote! {
+        pub mod #module {
+            /// The function library.
+            pub static LIBRARY: redis_lua::Library = redis_lua::Library::new(#name, #code);
+
+            #(#callers)*
+        }
+    }
+}
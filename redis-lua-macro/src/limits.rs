@@ -0,0 +1,338 @@
+use crate::{
+    ast::{ident, range},
+    check::Lint,
+};
+use full_moon::{
+    ast::{
+        Ast, Block, FunctionBody, GenericFor, LocalAssignment, LocalFunction, NumericFor,
+        Parameter, Prefix, Var,
+    },
+    visitors::Visitor,
+};
+use selene_lib::rules::Severity;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// The maximum number of active local variables in a Lua 5.1 function (`LUAI_MAXVARS`).
+pub const MAX_LOCALS: usize = 200;
+
+/// The maximum number of upvalues in a Lua 5.1 function (`LUAI_MAXUPVALUES`).
+pub const MAX_UPVALUES: usize = 60;
+
+/// Hidden local variables of `for` loops; the index, the limit and the step.
+const FOR_LOCALS: usize = 3;
+
+/// Local variables which the generated code declares in the function around the script,
+/// e.g. `keys`, `args` and `__internal_arg` of a library function.
+const PRELUDE_LOCALS: usize = 3;
+
+/// Local functions of the prelude which the script may reference as upvalues.
+const PRELUDE_FUNCTIONS: &[&str] = &["script_error"];
+
+/// Usage of local variables and upvalues of a script.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct Usage {
+    /// The maximum number of active locals in the top-level function
+    /// including the ones of the prelude but excluding arguments.
+    pub locals: usize,
+    /// The maximum number of upvalues of the nested functions
+    /// when each argument is a separate local variable.
+    pub upvalues: usize,
+}
+
+struct Function {
+    range: (usize, usize),
+    active: usize,
+    max: usize,
+    upvalues: HashSet<String>,
+}
+
+impl Function {
+    fn new(range: (usize, usize)) -> Self {
+        Self {
+            range,
+            active: 0,
+            max: 0,
+            upvalues: HashSet::new(),
+        }
+    }
+}
+
+struct Scope {
+    function: usize,
+    names: Vec<String>,
+    locals: usize,
+}
+
+struct Limits {
+    args: HashSet<String>,
+    functions: Vec<Function>,
+    scopes: Vec<Scope>,
+    usage: Usage,
+    lints: Vec<Lint>,
+}
+
+impl Limits {
+    fn new(args: &[String], range: (usize, usize)) -> Self {
+        let mut limits = Self {
+            args: args.iter().cloned().collect(),
+            // The chunk declaring the prelude and the function around the script.
+            functions: vec![Function::new(range), Function::new(range)],
+            scopes: vec![
+                Scope {
+                    function: 0,
+                    names: PRELUDE_FUNCTIONS.iter().map(|&name| name.into()).collect(),
+                    locals: 0,
+                },
+                // Arguments are locals of the top-level function but counted separately.
+                Scope {
+                    function: 1,
+                    names: args.to_vec(),
+                    locals: 0,
+                },
+            ],
+            usage: Usage::default(),
+            lints: Vec::new(),
+        };
+        limits.declare_hidden(PRELUDE_LOCALS);
+        limits
+    }
+
+    fn push_scope(&mut self) {
+        self.scopes.push(Scope {
+            function: self.functions.len() - 1,
+            names: Vec::new(),
+            locals: 0,
+        });
+    }
+
+    fn pop_scope(&mut self) {
+        if let Some(scope) = self.scopes.pop() {
+            self.functions[scope.function].active -= scope.locals;
+        }
+    }
+
+    fn declare_hidden(&mut self, count: usize) {
+        let scope = self.scopes.last_mut().unwrap();
+        scope.locals += count;
+
+        let function = &mut self.functions[scope.function];
+        function.active += count;
+        function.max = function.max.max(function.active);
+    }
+
+    fn declare(&mut self, name: &str) {
+        self.scopes.last_mut().unwrap().names.push(name.into());
+        self.declare_hidden(1);
+    }
+
+    fn reference(&mut self, name: &str) {
+        let owner = match self
+            .scopes
+            .iter()
+            .rev()
+            .find(|scope| scope.names.iter().any(|n| n == name))
+        {
+            Some(scope) => scope.function,
+            None => return,
+        };
+
+        // Lua 5.1 passes upvalues through all the functions in between.
+        for function in &mut self.functions[owner + 1..] {
+            function.upvalues.insert(name.into());
+        }
+    }
+
+    fn too_many(&mut self, range: (usize, usize), code: &'static str, message: String) {
+        self.lints.push(Lint {
+            range,
+            severity: Severity::Error,
+            code,
+            message,
+            notes: vec!["try splitting the function or grouping variables in a table".into()],
+        });
+    }
+
+    fn check_function(&mut self, function: Function) {
+        let args = function
+            .upvalues
+            .iter()
+            .filter(|name| self.args.contains(*name))
+            .count();
+        let others = function.upvalues.len() - args;
+
+        self.usage.upvalues = self.usage.upvalues.max(function.upvalues.len());
+
+        if function.max > MAX_LOCALS {
+            self.too_many(
+                function.range,
+                "too_many_locals",
+                format!(
+                    "function has {} local variables (limit is {})",
+                    function.max, MAX_LOCALS
+                ),
+            );
+        }
+        // Arguments can be passed as a single table, which takes one upvalue.
+        if others + args.min(1) > MAX_UPVALUES {
+            self.too_many(
+                function.range,
+                "too_many_upvalues",
+                format!(
+                    "function has {} upvalues (limit is {})",
+                    others + args.min(1),
+                    MAX_UPVALUES
+                ),
+            );
+        }
+    }
+
+    fn check_top(&mut self) {
+        let top = self.functions.pop().unwrap();
+        self.usage.locals = top.max;
+
+        // Arguments can be passed as a single table, which takes one local.
+        let locals = top.max + self.args.len().min(1);
+        if locals > MAX_LOCALS {
+            self.too_many(
+                top.range,
+                "too_many_locals",
+                format!(
+                    "script has {} local variables (limit is {})",
+                    locals, MAX_LOCALS
+                ),
+            );
+        }
+    }
+}
+
+impl<'ast> Visitor<'ast> for Limits {
+    fn visit_function_body(&mut self, body: &FunctionBody<'ast>) {
+        self.functions.push(Function::new(range(body)));
+        self.push_scope();
+
+        for param in body.iter_parameters() {
+            match param {
+                Parameter::Name(name) => self.declare(&ident(name).unwrap_or_default()),
+                Parameter::Ellipse(_) => self.declare_hidden(1),
+            }
+        }
+    }
+
+    fn visit_function_body_end(&mut self, _: &FunctionBody<'ast>) {
+        self.pop_scope();
+        let function = self.functions.pop().unwrap();
+        self.check_function(function);
+    }
+
+    fn visit_block(&mut self, _: &Block<'ast>) {
+        self.push_scope();
+    }
+
+    fn visit_block_end(&mut self, _: &Block<'ast>) {
+        self.pop_scope();
+    }
+
+    fn visit_local_assignment_end(&mut self, assign: &LocalAssignment<'ast>) {
+        // The names become visible after the expressions are evaluated.
+        for name in assign.name_list().iter() {
+            self.declare(&ident(name).unwrap_or_default());
+        }
+    }
+
+    fn visit_local_function(&mut self, func: &LocalFunction<'ast>) {
+        self.declare(&ident(func.name()).unwrap_or_default());
+    }
+
+    fn visit_numeric_for(&mut self, stmt: &NumericFor<'ast>) {
+        self.push_scope();
+        self.declare_hidden(FOR_LOCALS);
+        self.declare(&ident(stmt.index_variable()).unwrap_or_default());
+    }
+
+    fn visit_numeric_for_end(&mut self, _: &NumericFor<'ast>) {
+        self.pop_scope();
+    }
+
+    fn visit_generic_for(&mut self, stmt: &GenericFor<'ast>) {
+        self.push_scope();
+        self.declare_hidden(FOR_LOCALS);
+        for name in stmt.names().iter() {
+            self.declare(&ident(name).unwrap_or_default());
+        }
+    }
+
+    fn visit_generic_for_end(&mut self, _: &GenericFor<'ast>) {
+        self.pop_scope();
+    }
+
+    fn visit_var(&mut self, var: &Var<'ast>) {
+        if let Var::Name(name) = var {
+            self.reference(&ident(name).unwrap_or_default());
+        }
+    }
+
+    fn visit_prefix(&mut self, prefix: &Prefix<'ast>) {
+        if let Prefix::Name(name) = prefix {
+            self.reference(&ident(name).unwrap_or_default());
+        }
+    }
+}
+
+/// Count local variables and upvalues against the limits of Lua 5.1.
+///
+/// `args` are the internal variables holding the script arguments.
+pub fn check(ast: &Ast, args: &[String]) -> (Usage, Vec<Lint>) {
+    let mut limits = Limits::new(args, range(ast.nodes()));
+    limits.visit_ast(ast);
+    limits.check_top();
+    (limits.usage, limits.lints)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(code: &str, args: &[&str]) -> (Usage, Vec<&'static str>) {
+        let ast = full_moon::parse(code).unwrap();
+        let args: Vec<_> = args.iter().map(|arg| arg.to_string()).collect();
+        let (usage, lints) = check(&ast, &args);
+        (usage, lints.into_iter().map(|lint| lint.code).collect())
+    }
+
+    fn locals(count: usize) -> String {
+        (0..count)
+            .map(|i| format!("local v{} = {}\n", i, i))
+            .collect()
+    }
+
+    #[test]
+    fn prelude_locals() {
+        let (usage, lints) = codes(&locals(MAX_LOCALS - PRELUDE_LOCALS), &[]);
+        assert_eq!(usage.locals, MAX_LOCALS);
+        assert!(lints.is_empty());
+
+        let (_, lints) = codes(&locals(MAX_LOCALS - PRELUDE_LOCALS + 1), &[]);
+        assert_eq!(lints, vec!["too_many_locals"]);
+    }
+
+    #[test]
+    fn prelude_upvalues() {
+        let refs: Vec<_> = (0..MAX_UPVALUES).map(|i| format!("v{}", i)).collect();
+        let code = format!(
+            "{} local function f() return {} end",
+            locals(MAX_UPVALUES),
+            refs.join(" + ")
+        );
+        assert!(codes(&code, &[]).1.is_empty());
+
+        let code = format!(
+            "{} local function f() script_error('E') return {} end",
+            locals(MAX_UPVALUES),
+            refs.join(" + ")
+        );
+        let (usage, lints) = codes(&code, &[]);
+        assert_eq!(usage.upvalues, MAX_UPVALUES + 1);
+        assert_eq!(lints, vec!["too_many_upvalues"]);
+    }
+}
@@ -0,0 +1,237 @@
+//! Translation of Rust literals into Lua 5.1 literals.
+//!
+//! The script is given as Rust tokens, so string, char and number literals
+//! are written in Rust syntax, e.g. `"\u{1F600}"`, `r"raw"` or `1_000_000`.
+
+const INT_SUFFIXES: &[&str] = &[
+    "usize", "isize", "u128", "i128", "u64", "i64", "u32", "i32", "u16", "i16", "u8", "i8",
+];
+
+const FLOAT_SUFFIXES: &[&str] = &["f32", "f64"];
+
+/// Decode the escape sequences of a (non-raw) Rust string literal body.
+fn unescape(body: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    let mut chars = body.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => bytes.push(b'\n'),
+            Some('r') => bytes.push(b'\r'),
+            Some('t') => bytes.push(b'\t'),
+            Some('0') => bytes.push(0),
+            Some('\\') => bytes.push(b'\\'),
+            Some('\'') => bytes.push(b'\''),
+            Some('"') => bytes.push(b'"'),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                let b = u8::from_str_radix(&hex, 16)
+                    .map_err(|_| format!("invalid escape sequence `\\x{}`", hex))?;
+                bytes.push(b);
+            }
+            Some('u') => {
+                let code: String = chars
+                    .by_ref()
+                    .skip_while(|c| *c == '{')
+                    .take_while(|c| *c != '}')
+                    .filter(|c| *c != '_')
+                    .collect();
+                let c = u32::from_str_radix(&code, 16)
+                    .ok()
+                    .and_then(std::char::from_u32)
+                    .ok_or_else(|| format!("invalid escape sequence `\\u{{{}}}`", code))?;
+                let mut buf = [0; 4];
+                bytes.extend(c.encode_utf8(&mut buf).as_bytes());
+            }
+            // Line continuation skips the newline and the leading whitespaces.
+            Some('\n') | Some('\r') => {
+                while chars.peek().map_or(false, |c| c.is_whitespace()) {
+                    chars.next();
+                }
+            }
+            Some(c) => return Err(format!("unknown escape sequence `\\{}`", c)),
+            None => return Err("unterminated escape sequence".into()),
+        }
+    }
+
+    Ok(bytes)
+}
+
+/// Encode bytes as a double-quoted Lua string literal.
+fn lua_string(bytes: &[u8]) -> String {
+    let mut s = String::from("\"");
+    for b in bytes {
+        match *b {
+            b'\n' => s.push_str("\\n"),
+            b'\r' => s.push_str("\\r"),
+            b'\t' => s.push_str("\\t"),
+            b'"' => s.push_str("\\\""),
+            b'\\' => s.push_str("\\\\"),
+            // Always 3 digits so that the following digit isn't taken as a part of it.
+            b if b < 0x20 || b >= 0x7f => s.push_str(&format!("\\{:03}", b)),
+            b => s.push(b as char),
+        }
+    }
+    s.push('"');
+    s
+}
+
+/// The body of `r"..."` or `r#"..."#`
+fn raw_body(lit: &str) -> Option<&str> {
+    let rest = lit.strip_prefix('r')?;
+    let hashes = rest.chars().take_while(|c| *c == '#').count();
+    let rest = &rest[hashes..];
+    let body = rest.strip_prefix('"')?;
+    body.get(..body.len().checked_sub(1 + hashes)?)
+}
+
+/// The body of `"..."` or `'...'`
+fn quoted_body(lit: &str) -> Option<&str> {
+    let quote = lit.chars().next()?;
+    lit.strip_prefix(quote)?.strip_suffix(quote)
+}
+
+fn strip_suffix<'a>(num: &'a str, suffixes: &[&str]) -> &'a str {
+    suffixes
+        .iter()
+        .find_map(|s| num.strip_suffix(s))
+        .unwrap_or(num)
+}
+
+fn number(lit: &str) -> Result<String, String> {
+    let num = lit.replace('_', "");
+    let lower = num.to_lowercase();
+
+    let radix = match lower.get(..2) {
+        Some("0x") => 16,
+        Some("0o") => 8,
+        Some("0b") => 2,
+        _ => 10,
+    };
+
+    if radix == 10 {
+        // Floats such as `1e3f64` or `1.5f32`
+        let num = strip_suffix(strip_suffix(&num, INT_SUFFIXES), FLOAT_SUFFIXES);
+        if num.parse::<f64>().is_err() {
+            return Err(format!("invalid number literal `{}`", lit));
+        }
+        return Ok(num.into());
+    }
+
+    let digits = strip_suffix(&num[2..], INT_SUFFIXES);
+    let value = u128::from_str_radix(digits, radix)
+        .map_err(|_| format!("integer literal `{}` is out of range", lit))?;
+
+    if radix == 16 {
+        Ok(format!("0x{}", digits))
+    } else {
+        // Lua 5.1 has no binary or octal literals.
+        Ok(value.to_string())
+    }
+}
+
+/// Translate a Rust literal into the equivalent Lua literal.
+///
+/// Returns an error message if the literal has no Lua equivalent.
+pub fn translate(lit: &str) -> Result<String, String> {
+    let first = lit.chars().next().unwrap_or_default();
+
+    if first.is_ascii_digit() {
+        return number(lit);
+    }
+
+    // Byte literal `b'a'` is a number in Rust.
+    if let Some(body) = lit.strip_prefix('b').and_then(|b| {
+        if b.starts_with('\'') {
+            quoted_body(b)
+        } else {
+            None
+        }
+    }) {
+        return match unescape(body)?.as_slice() {
+            [b] => Ok(b.to_string()),
+            _ => Err(format!("invalid byte literal `{}`", lit)),
+        };
+    }
+
+    let (prefix, rest) = match lit.find(|c| c == '"' || c == '\'' || c == '#') {
+        Some(i) => lit.split_at(i),
+        None => return Err(format!("unknown literal `{}`", lit)),
+    };
+
+    let bytes = match prefix {
+        "" | "b" => {
+            let body =
+                quoted_body(rest).ok_or_else(|| format!("invalid string literal `{}`", lit))?;
+            // Keep the literal as is if it's already valid in Lua.
+            if !body.contains('\\') && !body.contains('\n') && !body.contains('\r') {
+                if rest.starts_with('"') || body.chars().count() == 1 {
+                    return Ok(rest.into());
+                }
+            }
+            unescape(body)?
+        }
+        "r" | "br" => raw_body(&lit[prefix.len() - 1..])
+            .ok_or_else(|| format!("invalid raw string literal `{}`", lit))?
+            .as_bytes()
+            .to_vec(),
+        "c" => {
+            let body =
+                quoted_body(rest).ok_or_else(|| format!("invalid string literal `{}`", lit))?;
+            let mut bytes = unescape(body)?;
+            bytes.push(0);
+            bytes
+        }
+        _ => return Err(format!("literal `{}` is not available in lua", lit)),
+    };
+
+    Ok(lua_string(&bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers() {
+        assert_eq!(translate("1_000").unwrap(), "1000");
+        assert_eq!(translate("10u64").unwrap(), "10");
+        assert_eq!(translate("1.5f32").unwrap(), "1.5");
+        assert_eq!(translate("1e3").unwrap(), "1e3");
+        assert_eq!(translate("0xff_u8").unwrap(), "0xff");
+        assert_eq!(translate("0o17").unwrap(), "15");
+        assert_eq!(translate("0b101").unwrap(), "5");
+        assert!(translate("0xffffffffffffffffffffffffffffffffff").is_err());
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(translate(r#""abc""#).unwrap(), r#""abc""#);
+        assert_eq!(translate("'a'").unwrap(), "'a'");
+        assert_eq!(translate(r#""a\tb""#).unwrap(), r#""a\tb""#);
+        assert_eq!(
+            translate(r#""\u{1F600}""#).unwrap(),
+            r#""\240\159\152\128""#
+        );
+        assert_eq!(translate(r#""\x7f""#).unwrap(), r#""\127""#);
+        assert_eq!(translate(r##"r#"a"b\n"#"##).unwrap(), r#""a\"b\\n""#);
+        assert_eq!(translate(r#"b"ab""#).unwrap(), r#""ab""#);
+        assert_eq!(translate(r#"c"ab""#).unwrap(), r#""ab\000""#);
+        assert_eq!(translate("'\\''").unwrap(), r#""'""#);
+        assert_eq!(translate("b'a'").unwrap(), "97");
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(translate(r#""\q""#).is_err());
+        assert!(translate(r#""\u{110000}""#).is_err());
+        assert!(translate("b'ab'").is_err());
+        assert!(translate(r#"x"a""#).is_err());
+    }
+}
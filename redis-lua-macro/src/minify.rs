@@ -0,0 +1,68 @@
+//! Minification of the emitted lua script.
+//!
+//! Comments and redundant whitespaces are removed, but line breaks are kept
+//! so that the line numbers reported by Redis still map to the Rust source code.
+
+use crate::lexer::{lex, Lexeme};
+
+/// Returns `true` if the two tokens need a space in between not to be merged,
+/// e.g. `local x`, `1 ..` or `- -`.
+fn needs_space(a: &Lexeme, b: &Lexeme) -> bool {
+    let joined = format!("{}{}", a.text, b.text);
+    let tokens = lex(&joined);
+
+    !(tokens.len() == 2 && tokens[0].text == a.text && tokens[1].text == b.text)
+}
+
+/// Minify the lua script keeping the line structure.
+pub fn minify(script: &str) -> String {
+    let tokens = lex(script);
+    let mut out = String::with_capacity(script.len());
+    let mut prev: Option<&Lexeme> = None;
+
+    for t in &tokens {
+        let gap = match prev {
+            Some(p) => &script[p.range.1..t.range.0],
+            None => &script[..t.range.0],
+        };
+        let lines = gap.matches('\n').count();
+
+        if lines > 0 {
+            out.push_str(&"\n".repeat(lines));
+        } else if prev.map_or(false, |p| needs_space(p, t)) {
+            out.push(' ');
+        }
+        out.push_str(t.text);
+        prev = Some(t);
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removes_spaces_and_comments() {
+        assert_eq!(minify("local  x = ( 1 + 2 ) -- sum"), "local x=(1+2)");
+        assert_eq!(minify("return {a , b}"), "return{a,b}");
+    }
+
+    #[test]
+    fn keeps_separators() {
+        assert_eq!(minify("return 1 .. 2"), "return 1 ..2");
+        assert_eq!(minify("return - -x"), "return- -x");
+        assert_eq!(minify("return a - b"), "return a-b");
+        assert_eq!(minify("return \"a\" .. b"), "return\"a\"..b");
+    }
+
+    #[test]
+    fn keeps_lines() {
+        assert_eq!(
+            minify("local x = 1\n\n  --[[\n]] return x"),
+            "local x=1\n\n\nreturn x"
+        );
+        assert_eq!(minify("return [[a  b]]"), "return[[a  b]]");
+    }
+}
@@ -0,0 +1,268 @@
+use crate::{
+    ast::{call, ident, is_nil, range, redis_command, value, var_name},
+    check::Lint,
+};
+use full_moon::ast::{Ast, Block, Expression, Field, FunctionBody, LastStmt, Stmt, Value, Var};
+use selene_lib::rules::Severity;
+use std::collections::{HashMap, HashSet};
+
+/// Commands which reply nil, e.g. when the key doesn't exist.
+const NIL_COMMANDS: &[&str] = &[
+    "get",
+    "getdel",
+    "getex",
+    "getset",
+    "hget",
+    "hrandfield",
+    "lindex",
+    "lmove",
+    "lpop",
+    "lpos",
+    "rpop",
+    "rpoplpush",
+    "spop",
+    "srandmember",
+    "zrank",
+    "zrevrank",
+    "zscore",
+];
+
+/// Detects tables returned to Redis which may contain `nil`.
+///
+/// Redis converts a Lua array to a multi-bulk reply only up to the first `nil`,
+/// so the rest of the elements are silently dropped.
+struct NilHoles<'a> {
+    /// The local variables holding the `$`/`@` arguments.
+    args: HashSet<&'a str>,
+    /// Local variables which may hold `nil`, with the reason.
+    scopes: Vec<HashMap<String, Option<String>>>,
+    lints: Vec<Lint>,
+}
+
+impl<'a> NilHoles<'a> {
+    fn new(args: &'a [String]) -> Self {
+        // An argument is `nil` if its value is `None`, until it's reassigned,
+        // e.g. by `$x = $x or false`.
+        let scope = args
+            .iter()
+            .map(|arg| (arg.clone(), Some("nil if it's `None`".into())))
+            .collect();
+
+        Self {
+            args: args.iter().map(|arg| arg.as_str()).collect(),
+            scopes: vec![scope],
+            lints: Vec::new(),
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Option<String>> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    fn maybe_nil(&self, expr: &Expression) -> Option<String> {
+        if is_nil(expr) {
+            return Some("`nil`".into());
+        }
+        if let Some(name) = var_name(expr) {
+            // The internal name of an argument means nothing to the user;
+            // the warning points at the argument anyway.
+            let is_arg = self.args.contains(name.as_str());
+            return self.lookup(&name).cloned().flatten().map(|reason| {
+                if is_arg {
+                    format!("the argument ({})", reason)
+                } else {
+                    format!("`{}` ({})", name, reason)
+                }
+            });
+        }
+        let command = call(expr).and_then(redis_command)?;
+        if NIL_COMMANDS.contains(&command.as_str()) {
+            Some(format!("`redis.call(\"{}\", ...)`", command))
+        } else {
+            None
+        }
+    }
+
+    fn block(&mut self, block: &Block, in_function: bool) {
+        self.scopes.push(HashMap::new());
+
+        for stmt in block.iter_stmts() {
+            self.stmt(stmt, in_function);
+        }
+
+        if let Some(LastStmt::Return(ret)) = block.last_stmts() {
+            if !in_function {
+                for expr in ret.returns().iter() {
+                    self.returned(expr);
+                }
+            }
+        }
+
+        self.scopes.pop();
+    }
+
+    fn function(&mut self, body: &FunctionBody) {
+        // Parameters and upvalues are not tracked inside functions.
+        let scopes = std::mem::replace(&mut self.scopes, vec![HashMap::new()]);
+        self.block(body.block(), true);
+        self.scopes = scopes;
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, in_function: bool) {
+        match stmt {
+            Stmt::LocalAssignment(assign) => {
+                let exprs: Vec<_> = assign.expr_list().iter().collect();
+                let values: Vec<_> = assign
+                    .name_list()
+                    .iter()
+                    .enumerate()
+                    .map(|(i, name)| {
+                        let reason = match exprs.get(i) {
+                            Some(expr) => self.maybe_nil(expr),
+                            // The last call may return multiple values.
+                            None if exprs.last().and_then(|e| call(e)).is_some() => None,
+                            None => Some("declared without a value".into()),
+                        };
+                        (ident(name).unwrap_or_default(), reason)
+                    })
+                    .collect();
+                self.scopes.last_mut().unwrap().extend(values);
+                for expr in exprs {
+                    self.expr(expr);
+                }
+            }
+            Stmt::Assignment(assign) => {
+                let exprs: Vec<_> = assign.expr_list().iter().collect();
+                for (i, var) in assign.var_list().iter().enumerate() {
+                    let name = match var {
+                        Var::Name(name) => ident(name).unwrap_or_default(),
+                        _ => continue,
+                    };
+                    let reason = exprs.get(i).and_then(|expr| self.maybe_nil(expr));
+                    // Assignments in branches are treated as unconditional
+                    // so that `if not v then v = "" end` is not reported.
+                    if let Some(scope) = self
+                        .scopes
+                        .iter_mut()
+                        .rev()
+                        .find(|scope| scope.contains_key(&name))
+                    {
+                        scope.insert(name, reason);
+                    }
+                }
+                for expr in exprs {
+                    self.expr(expr);
+                }
+            }
+            Stmt::Do(stmt) => self.block(stmt.block(), in_function),
+            Stmt::GenericFor(stmt) => self.block(stmt.block(), in_function),
+            Stmt::NumericFor(stmt) => self.block(stmt.block(), in_function),
+            Stmt::Repeat(stmt) => self.block(stmt.block(), in_function),
+            Stmt::While(stmt) => self.block(stmt.block(), in_function),
+            Stmt::If(stmt) => {
+                self.block(stmt.block(), in_function);
+                for else_if in stmt.else_if().into_iter().flatten() {
+                    self.block(else_if.block(), in_function);
+                }
+                if let Some(block) = stmt.else_block() {
+                    self.block(block, in_function);
+                }
+            }
+            Stmt::FunctionDeclaration(decl) => self.function(decl.body()),
+            Stmt::LocalFunction(func) => self.function(func.func_body()),
+            _ => {}
+        }
+    }
+
+    fn expr(&mut self, expr: &Expression) {
+        if let Some(Value::Function((_, body))) = value(expr) {
+            self.function(body);
+        }
+    }
+
+    fn returned(&mut self, expr: &Expression) {
+        let table = match value(expr) {
+            Some(Value::TableConstructor(table)) => table,
+            _ => return,
+        };
+
+        for (field, _) in table.iter_fields() {
+            let expr = match field {
+                Field::NoKey(expr) => expr,
+                _ => continue,
+            };
+            if let Some(reason) = self.maybe_nil(expr) {
+                self.lints.push(Lint {
+                    range: range(expr),
+                    severity: Severity::Warning,
+                    code: "nil_in_returned_table",
+                    message: format!("{} may be nil in the returned table", reason),
+                    notes: vec![
+                        "Redis truncates the array reply at the first nil".into(),
+                        "try `false` instead, which Redis replies as nil".into(),
+                    ],
+                });
+            }
+        }
+    }
+}
+
+pub fn check(ast: &Ast, args: &[String]) -> Vec<Lint> {
+    let mut nil = NilHoles::new(args);
+    nil.block(ast.nodes(), false);
+    nil.lints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lints(code: &str, args: &[&str]) -> Vec<String> {
+        let ast = full_moon::parse(code).unwrap();
+        let args: Vec<_> = args.iter().map(|arg| arg.to_string()).collect();
+        check(&ast, &args)
+            .into_iter()
+            .map(|lint| lint.message)
+            .collect()
+    }
+
+    #[test]
+    fn explicit_nil() {
+        assert_eq!(
+            lints("return {1, nil, 3}", &[]),
+            vec!["`nil` may be nil in the returned table"]
+        );
+    }
+
+    #[test]
+    fn nil_command() {
+        assert_eq!(
+            lints("local v = redis.call(\"GET\", \"k\") return {v}", &[]),
+            vec!["`v` (`redis.call(\"get\", ...)`) may be nil in the returned table"]
+        );
+        assert!(lints("return {redis.call(\"INCR\", \"k\")}", &[]).is_empty());
+    }
+
+    #[test]
+    fn argument() {
+        assert_eq!(
+            lints("return {__a1, 2}", &["__a1"]),
+            vec!["the argument (nil if it's `None`) may be nil in the returned table"]
+        );
+        assert_eq!(lints("local v = __a1 return {v}", &["__a1"]).len(), 1);
+    }
+
+    #[test]
+    fn guarded() {
+        assert!(lints("return {__a1 or false}", &["__a1"]).is_empty());
+        assert!(lints("__a1 = __a1 or false return {__a1}", &["__a1"]).is_empty());
+        let code = "local v = redis.call(\"GET\", \"k\") if not v then v = \"\" end return {v}";
+        assert!(lints(code, &[]).is_empty());
+        assert!(lints("return {__a1}", &[]).is_empty());
+    }
+
+    #[test]
+    fn inside_function() {
+        assert!(lints("local function f() return {nil} end return f()", &[]).is_empty());
+    }
+}
@@ -23,7 +23,9 @@ pub fn vars(script: &Script) -> impl Iterator<Item = (usize, &Arg)> {
 }
 
 pub fn to_name((_index, arg): (usize, &Arg)) -> TokenStream {
-    new_ident(arg.as_rust().to_string())
+    // Keep the identifier as is, which may be raw, e.g. `r#end`.
+    let s: TokenStream1 = arg.as_rust().clone().into();
+    s.into()
 }
 
 pub fn to_type((index, _): (usize, &Arg)) -> TokenStream {
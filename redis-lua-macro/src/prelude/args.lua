@@ -0,0 +1 @@
+local __internal_flags = ARGV[#ARGV] local function __internal_arg(i) if string.byte(__internal_flags, i) == 49 then return cmsgpack.unpack(ARGV[i]) end return ARGV[i] end
\ No newline at end of file
@@ -0,0 +1 @@
+local __f=ARGV[#ARGV] local function __g(i)if string.byte(__f,i)==49 then return cmsgpack.unpack(ARGV[i])end return ARGV[i]end
\ No newline at end of file
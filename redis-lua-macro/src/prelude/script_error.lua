@@ -0,0 +1 @@
+local function script_error(tag, fields) if fields == nil then return redis.error_reply(tag) end local hex = string.gsub(cmsgpack.pack(fields), '.', function(c) return string.format('%02x', string.byte(c)) end) return redis.error_reply(tag .. ' ' .. hex) end
\ No newline at end of file
@@ -0,0 +1 @@
+local function script_error(t,f)if f==nil then return redis.error_reply(t)end local h=string.gsub(cmsgpack.pack(f),'.',function(c)return string.format('%02x',string.byte(c))end)return redis.error_reply(t..' '..h)end
\ No newline at end of file
@@ -0,0 +1 @@
+local function __internal_unpack(args) local flags = args[#args] return function(i) if string.byte(flags, i) == 49 then return cmsgpack.unpack(args[i]) end return args[i] end end
\ No newline at end of file
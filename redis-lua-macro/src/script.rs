@@ -1,7 +1,9 @@
 use crate::{
+    literal,
     proc_macro::{Span, TokenStream, TokenTree},
     token::{retokenize, Pos, Token},
 };
+use proc_macro_error::{Diagnostic, Level};
 use std::collections::BTreeMap;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -15,17 +17,17 @@ pub struct Arg {
     key: Token,
     rust: TokenTree,
     lua: String,
-    argv: String,
+    table: String,
     atype: ArgType,
 }
 
 impl Arg {
-    fn new(key: Token, rust: TokenTree, lua: String, argv: String, atype: ArgType) -> Self {
+    fn new(key: Token, rust: TokenTree, lua: String, table: String, atype: ArgType) -> Self {
         Self {
             key,
             rust,
             lua,
-            argv,
+            table,
             atype,
         }
     }
@@ -40,14 +42,14 @@ impl Arg {
         &self.rust
     }
 
-    /// As lua internal variable, e.g. `__internal_from_args1`
+    /// As lua internal variable, e.g. `__internal_from_args_1`
     pub fn as_lua(&self) -> &str {
         &self.lua
     }
 
-    /// As `ARGV` parameter, e.g. `ARGV[1]`
-    pub fn as_argv(&self) -> &str {
-        &self.argv
+    /// As an element of the argument table, e.g. `__internal_args[1]`
+    pub fn as_table(&self) -> &str {
+        &self.table
     }
 
     pub fn atype(&self) -> ArgType {
@@ -55,6 +57,106 @@ impl Arg {
     }
 }
 
+/// Prefix of the local variables holding the arguments.
+#[cfg(not(feature = "minify"))]
+const ARG_PREFIX: &str = "__internal_from_args_";
+#[cfg(feature = "minify")]
+const ARG_PREFIX: &str = "__";
+
+/// The table holding the arguments, which is passed to `redis_lua::Info`.
+#[cfg(not(feature = "minify"))]
+pub const ARG_TABLE: &str = "__internal_args";
+#[cfg(feature = "minify")]
+pub const ARG_TABLE: &str = "__a";
+
+/// Lua keywords; allowed as names only in the raw form, e.g. `$r#end`.
+const LUA_KEYWORDS: &[&str] = &[
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "if", "in", "local",
+    "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+/// Rust keywords which can't be identifiers.
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while",
+];
+
+/// Rust keywords which can't be raw identifiers either.
+const NON_RAW_KEYWORDS: &[&str] = &["crate", "self", "Self", "super"];
+
+/// Methods of the script objects which the setters of `$` variables would collide with.
+const RESERVED_METHODS: &[&str] = &[
+    "add",
+    "chain",
+    "clone",
+    "clone_from",
+    "info",
+    "invoke",
+    "invoke_async",
+    "invoke_options",
+    "invoke_on_all_primaries",
+    "join",
+    "new",
+    "raising",
+    "route",
+    "route_keys",
+    "take",
+    "with_options",
+];
+
+fn name_error(token: &Token, msg: String, note: Option<String>) {
+    let msg = format!("in lua: {} (invalid_name)", msg);
+    let diag = Diagnostic::spanned(token.span().into(), Level::Error, msg);
+    match note {
+        Some(note) => diag.note(note).emit(),
+        None => diag.emit(),
+    }
+}
+
+/// Check the name of `$` variables and `@` captures.
+fn validate(token: &Token) {
+    let sigil = if token.is_cap() { "@" } else { "$" };
+    let source = token.to_string();
+
+    if !matches!(token.tree(), TokenTree::Ident(_)) {
+        let msg = format!(
+            "expected an identifier after `{}`, found `{}`",
+            sigil, source
+        );
+        return name_error(token, msg, None);
+    }
+
+    // `r#end` escapes the keyword check.
+    let (name, raw) = match source.strip_prefix("r#") {
+        Some(name) => (name, true),
+        None => (source.as_str(), false),
+    };
+    let placeholder = format!("{}{}", sigil, source);
+
+    if !raw && LUA_KEYWORDS.contains(&name) {
+        let msg = format!("`{}` is a lua keyword", placeholder);
+        let note = format!("use `{}r#{}` if this is intended", sigil, name);
+        name_error(token, msg, Some(note));
+    } else if !raw && RUST_KEYWORDS.contains(&name) {
+        let msg = format!("`{}` is a rust keyword", placeholder);
+        let note = if NON_RAW_KEYWORDS.contains(&name) {
+            "try another name".into()
+        } else {
+            format!("use `{}r#{}` if this is intended", sigil, name)
+        };
+        name_error(token, msg, Some(note));
+    } else if token.is_var() && RESERVED_METHODS.contains(&name) {
+        let msg = format!(
+            "`{}` collides with the method `{}` of the script object",
+            placeholder, name
+        );
+        let note = format!("rename the variable, e.g. `${}_`", name);
+        name_error(token, msg, Some(note));
+    }
+}
+
 #[derive(Debug)]
 pub struct Args(Vec<Arg>);
 
@@ -71,15 +173,15 @@ impl Args {
             Some(arg) => arg.clone(),
             None => {
                 let rust = tt.clone();
-                let lua = format!("__internal_from_args_{}", self.0.len());
-                let argv = format!("ARGV[{}]", self.0.len() + 1);
+                let lua = format!("{}{}", ARG_PREFIX, self.0.len());
+                let table = format!("{}[{}]", ARG_TABLE, self.0.len() + 1);
                 let atype = if token.is_cap() {
                     ArgType::Cap
                 } else {
                     ArgType::Var
                 };
 
-                let arg = Arg::new(key, rust, lua, argv, atype);
+                let arg = Arg::new(key, rust, lua, table, atype);
                 self.0.push(arg.clone());
                 arg
             }
@@ -94,8 +196,9 @@ impl Args {
 #[derive(Debug)]
 pub struct Script {
     script: String,
-    wrapped: String,
+    table: String,
     spans: BTreeMap<usize, Span>,
+    location: (String, usize, usize),
     args: Args,
 }
 
@@ -106,6 +209,9 @@ impl Script {
         // Script string to be checked & emitted.
         let mut script = String::new();
 
+        // Same as `script` but the arguments are referred through a table.
+        let mut table = String::new();
+
         // Table to map lua code span to rust code span.
         let mut spans = BTreeMap::new();
 
@@ -114,12 +220,27 @@ impl Script {
 
         let mut pos = Option::<Pos>::None;
 
+        // Column of the first token, which is the left edge of the script.
+        let mut base = Option::<usize>::None;
+
+        // File, line and column of the first token.
+        let mut location = Option::<(String, usize, usize)>::None;
+
         for t in tokens {
-            let (code, span) = if t.is_arg() && convert_args {
+            let (code, table_code, span) = if t.is_arg() && convert_args {
+                validate(&t);
                 let arg = args.add(&t);
-                (arg.as_lua().into(), t.span())
+                (arg.as_lua().into(), arg.as_table().into(), t.span())
+            } else if let TokenTree::Literal(_) = t.tree() {
+                // Rust literal syntax, e.g. `1_000` or `"\u{1F600}"`, is not valid in Lua.
+                let code = literal::translate(&t.to_string()).unwrap_or_else(|msg| {
+                    let msg = format!("in lua: {} (literal)", msg);
+                    Diagnostic::spanned(t.span().into(), Level::Error, msg).emit();
+                    t.to_string()
+                });
+                (code.clone(), code, t.span())
             } else {
-                (t.to_string(), t.span())
+                (t.to_string(), t.to_string(), t.span())
             };
 
             let (line, col) = (t.start().line, t.start().column);
@@ -127,14 +248,22 @@ impl Script {
                 .take()
                 .map(|lc| (lc.line, lc.column))
                 .unwrap_or_else(|| (line, col));
+            let base = *base.get_or_insert(col);
+            location.get_or_insert_with(|| (t.span().file(), line, col));
 
-            if line > prev_line {
-                script.push_str("\n");
+            // Keep the line structure of the Rust source so that line numbers
+            // of the lua script stay relative to the macro invocation.
+            let space = if line > prev_line {
+                "\n".repeat(line - prev_line) + &" ".repeat(col.saturating_sub(base))
             } else if line == prev_line {
-                for _ in 0..col.saturating_sub(prev_col) {
-                    script.push_str(" ");
-                }
-            }
+                " ".repeat(col.saturating_sub(prev_col))
+            } else {
+                String::new()
+            };
+            script.push_str(&space);
+            table.push_str(&space);
+            table.push_str(&table_code);
+
             let begin = script.len();
             script.push_str(&code.to_string());
             let end = script.len();
@@ -147,32 +276,18 @@ impl Script {
         }
 
         let script = script.trim_end().to_string();
+        let table = table.trim_end().to_string();
 
-        // `wrapped` contains `script` plus variable initialization logic at the top.
-        // Only `script` part is checked by the linter. The linter is configured
-        // so that it allows only special local variables like `__internal_0` but doesn't
-        // allow `ARGV`. This is to prevent script authers from accidentally writing
-        // `ARGV[x]` where `x` is larger than actual arguments given by a command.
-        let wrapped = if convert_args {
-            let wrapper = args.args().iter().fold(String::new(), |s, arg| {
-                // Generating these lines.
-                //
-                // ```
-                // local __internal_0 = ARGV[0];
-                // local __internal_1 = ARGV[1];
-                // local __internal_2 = ARGV[2];
-                // ```
-                s + &format!("local {} = {}; ", arg.as_lua(), arg.as_argv())
-            });
-            format!("{}\n{}", wrapper, script)
-        } else {
-            "".into()
-        };
+        let location = location.unwrap_or_else(|| {
+            let span = Span::call_site();
+            (span.file(), span.line(), span.column())
+        });
 
         Self {
             script,
-            wrapped,
+            table,
             spans,
+            location,
             args,
         }
     }
@@ -181,14 +296,21 @@ impl Script {
         &self.script
     }
 
-    pub fn wrap(&self) -> &str {
-        &self.wrapped
+    /// The script referring the arguments through `__internal_args` table.
+    pub fn table(&self) -> &str {
+        &self.table
     }
 
     pub fn args(&self) -> &[Arg] {
         self.args.args()
     }
 
+    /// The file, line and column where the script starts in the Rust source code.
+    pub fn location(&self) -> (&str, usize, usize) {
+        let (file, line, column) = &self.location;
+        (file, *line, *column)
+    }
+
     /// Convert lua code span to rust code span.
     pub fn range_to_span(&self, range: (usize, usize)) -> Vec<Span> {
         self.spans
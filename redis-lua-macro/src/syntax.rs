@@ -0,0 +1,195 @@
+use crate::{
+    check::Lint,
+    lexer::{lex, Kind, Lexeme},
+};
+use selene_lib::rules::Severity;
+
+/// The largest integer which a Lua 5.1 number (double) represents exactly.
+const MAX_EXACT_INTEGER: u128 = 1 << 53;
+
+fn unsupported(t: &Lexeme, what: &str, note: &str) -> Lint {
+    Lint {
+        range: t.range,
+        severity: Severity::Error,
+        code: "unsupported_syntax",
+        message: format!("{} is not available in Redis Lua 5.1", what),
+        notes: vec![note.into()],
+    }
+}
+
+fn bitwise(t: &Lexeme) -> Option<Lint> {
+    let func = match t.text {
+        "&" => "bit.band(a, b)",
+        "|" => "bit.bor(a, b)",
+        "~" => "bit.bxor(a, b) or bit.bnot(a)",
+        "<<" => "bit.lshift(a, n)",
+        ">>" => "bit.rshift(a, n)",
+        _ => return None,
+    };
+    Some(unsupported(
+        t,
+        &format!("bitwise operator `{}`", t.text),
+        &format!("try `{}` of the `bit` library", func),
+    ))
+}
+
+fn number(t: &Lexeme) -> Option<Lint> {
+    let text = t.text.to_lowercase();
+
+    let value = if let Some(hex) = text.strip_prefix("0x") {
+        if hex.contains('.') || hex.contains('p') {
+            return Some(unsupported(
+                t,
+                "hexadecimal float literal",
+                "try a decimal literal instead",
+            ));
+        }
+        u128::from_str_radix(hex, 16).ok()?
+    } else {
+        text.parse().ok()?
+    };
+
+    if value > MAX_EXACT_INTEGER {
+        Some(Lint {
+            range: t.range,
+            severity: Severity::Error,
+            code: "unsupported_syntax",
+            message: format!(
+                "integer literal `{}` cannot be represented exactly in Redis Lua 5.1",
+                t.text
+            ),
+            notes: vec!["numbers in Lua 5.1 are double precision floats; try a string".into()],
+        })
+    } else {
+        None
+    }
+}
+
+fn escape(t: &Lexeme) -> Option<Lint> {
+    if !t.text.starts_with('"') && !t.text.starts_with('\'') {
+        return None;
+    }
+
+    let mut chars = t.text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            continue;
+        }
+        let (what, note) = match chars.next() {
+            Some('x') => (
+                "escape sequence `\\x`",
+                "try a decimal escape such as `\\255`",
+            ),
+            Some('u') => (
+                "escape sequence `\\u{...}`",
+                "try decimal escapes of the UTF-8 bytes such as `\\240\\159\\152\\128`",
+            ),
+            Some('z') => (
+                "escape sequence `\\z`",
+                "try concatenating strings with `..`",
+            ),
+            _ => continue,
+        };
+        return Some(unsupported(t, what, note));
+    }
+    None
+}
+
+/// Detects syntax of Lua 5.2 or later which `full_moon` may accept but Redis doesn't.
+pub fn check(script: &str) -> Vec<Lint> {
+    let tokens = lex(script);
+    let mut lints = Vec::new();
+
+    for (i, t) in tokens.iter().enumerate() {
+        let next = tokens.get(i + 1);
+
+        let lint = match t.kind {
+            Kind::Name if t.text == "goto" && next.map(|n| n.kind) == Some(Kind::Name) => Some(
+                unsupported(t, "`goto`", "try `break` or restructuring the loop"),
+            ),
+            Kind::Symbol if t.is("::") => Some(unsupported(
+                t,
+                "label `::name::`",
+                "labels are only usable with `goto`",
+            )),
+            Kind::Symbol
+                if t.is("<")
+                    && next.map_or(false, |n| n.is("const") || n.is("close"))
+                    && tokens.get(i + 2).map_or(false, |n| n.is(">")) =>
+            {
+                Some(unsupported(
+                    t,
+                    &format!("attribute `<{}>`", next.unwrap().text),
+                    "remove the attribute",
+                ))
+            }
+            Kind::Symbol => bitwise(t),
+            Kind::Number => number(t),
+            Kind::String => escape(t),
+            _ => None,
+        };
+
+        lints.extend(lint);
+    }
+
+    lints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(script: &str) -> Vec<String> {
+        check(script).into_iter().map(|lint| lint.message).collect()
+    }
+
+    #[test]
+    fn unsupported_syntax() {
+        let cases = &[
+            ("goto done", "`goto`"),
+            ("::done::", "label `::name::`"),
+            ("local x <const> = 1", "attribute `<const>`"),
+            ("return a & b", "bitwise operator `&`"),
+            ("return a << 2", "bitwise operator `<<`"),
+            ("return ~a", "bitwise operator `~`"),
+            ("return 0x1p4", "hexadecimal float literal"),
+            (r#"return "\x41""#, "escape sequence `\\x`"),
+            (r#"return "\u{41}""#, "escape sequence `\\u{...}`"),
+            (r#"return "a\z b""#, "escape sequence `\\z`"),
+        ];
+        for (script, what) in cases {
+            let expected = format!("{} is not available in Redis Lua 5.1", what);
+            let messages = messages(script);
+            assert!(!messages.is_empty(), "{}", script);
+            assert!(messages.iter().all(|m| *m == expected), "{}", script);
+        }
+    }
+
+    #[test]
+    fn inexact_integer() {
+        assert_eq!(
+            messages("return 9007199254740993"),
+            vec![
+                "integer literal `9007199254740993` cannot be represented exactly in Redis Lua 5.1"
+            ]
+        );
+        assert!(messages("return 9007199254740992").is_empty());
+        assert!(messages("return 0x20000000000000").is_empty());
+    }
+
+    #[test]
+    fn supported_syntax() {
+        let cases = &[
+            "local goto = 1 return goto",
+            "return a ~= b and a <= b and a .. b",
+            "return x < y, y > z",
+            "return 1.5e3, 0xff",
+            r#"return "\65\n", '\\x'"#,
+            r#"return [[\x41 & a]]"#,
+            "-- goto done & ::x::",
+        ];
+        for script in cases {
+            assert!(messages(script).is_empty(), "{}", script);
+        }
+    }
+}
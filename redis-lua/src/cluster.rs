@@ -0,0 +1,105 @@
+use crate::{
+    error::Error,
+    options::Options,
+    script::{Info, Script},
+    types::ScriptArg,
+};
+
+/// The number of hash slots in Redis Cluster.
+const SLOTS: u16 = 16384;
+
+/// CRC16 (XMODEM) used by Redis Cluster.
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |crc, &b| {
+        (0..8).fold(crc ^ ((b as u16) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+/// The hash slot of the key in Redis Cluster.
+///
+/// If the key contains a non-empty hashtag, e.g. `{user1000}.following`,
+/// only the part inside the first `{...}` is hashed.
+pub fn slot(key: &[u8]) -> u16 {
+    let tag = key.iter().position(|&b| b == b'{').and_then(|open| {
+        let rest = &key[open + 1..];
+        let close = rest.iter().position(|&b| b == b'}')?;
+        Some(&rest[..close]).filter(|tag| !tag.is_empty())
+    });
+
+    crc16(tag.unwrap_or(key)) % SLOTS
+}
+
+/// The hash slot of the keys; an error if they span multiple slots.
+pub(crate) fn check_slot(keys: &[Vec<u8>]) -> Result<Option<u16>, Error> {
+    let mut slots = keys.iter().map(|key| (key, slot(key)));
+    let (first, expected) = match slots.next() {
+        Some(first) => first,
+        None => return Ok(None),
+    };
+
+    match slots.find(|(_, slot)| *slot != expected) {
+        Some((key, slot)) => {
+            let detail = format!(
+                "`{}` is in slot {} but `{}` is in slot {}",
+                String::from_utf8_lossy(first),
+                expected,
+                String::from_utf8_lossy(key),
+                slot
+            );
+            let err = redis::RedisError::from((
+                redis::ErrorKind::CrossSlot,
+                "Keys don't hash to the same slot; use a hashtag, e.g. `{user1}`",
+                detail,
+            ));
+            Err(Error::new(err, None))
+        }
+        None => Ok(Some(expected)),
+    }
+}
+
+/// Script routed to the node owning the slot of the keys.
+///
+/// The keys are passed as `KEYS` on every path invoking the script, e.g. `invoke`,
+/// [`ScriptPipeline`](crate::ScriptPipeline) or after `with_options`, and they are
+/// checked to hash to the same slot before the script is sent.
+pub struct Routed<S> {
+    script: S,
+    keys: Vec<Vec<u8>>,
+}
+
+impl<S> Routed<S> {
+    pub(crate) fn new(script: S, keys: Vec<Vec<u8>>) -> Self {
+        Self { script, keys }
+    }
+}
+
+impl<S: Script> Routed<S> {
+    /// The hash slot of the keys including the ones of the inner script;
+    /// an error if they span multiple slots.
+    pub fn slot(&self) -> Result<Option<u16>, Error> {
+        let mut keys = vec![];
+        self.route_keys(&mut keys);
+        check_slot(&keys)
+    }
+}
+
+impl<S: Script> Script for Routed<S> {
+    fn info(&self, info: &mut Vec<Info>, args: &mut Vec<ScriptArg>) {
+        self.script.info(info, args);
+    }
+
+    fn route_keys(&self, keys: &mut Vec<Vec<u8>>) {
+        keys.extend(self.keys.iter().cloned());
+        self.script.route_keys(keys);
+    }
+
+    fn invoke_options(&self) -> Option<&Options> {
+        self.script.invoke_options()
+    }
+}
@@ -0,0 +1,246 @@
+use crate::lines::{script_line, LineMap, Location};
+use std::fmt::{self, Display};
+
+/// Details of the failure common to all the kinds of errors.
+#[derive(Debug)]
+pub struct Details {
+    raw: redis::RedisError,
+    message: String,
+    fragment: Option<usize>,
+    location: Option<Location>,
+}
+
+impl Details {
+    fn new(raw: redis::RedisError, kind: &Kind, lines: Option<&LineMap>) -> Self {
+        let detail = raw.detail().unwrap_or_default();
+        let line = script_line(detail);
+
+        // `redis.error_reply("MYERR ...")` is split into the code and the detail by `redis-rs`.
+        let message = match (kind, raw.kind(), raw.code()) {
+            (Kind::ErrorReply, redis::ErrorKind::ExtensionError, Some(code)) => {
+                format!("{} {}", code, lua_message(detail))
+                    .trim_end()
+                    .to_string()
+            }
+            _ => lua_message(detail),
+        };
+
+        Self {
+            message,
+            fragment: line.and_then(|line| lines?.fragment(line)),
+            location: line.and_then(|line| lines?.locate(line)),
+            raw,
+        }
+    }
+
+    /// The original error from `redis-rs`.
+    pub fn raw(&self) -> &redis::RedisError {
+        &self.raw
+    }
+
+    /// The error message without the script name and line number added by Redis.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The index of the joined script which failed, if known.
+    pub fn fragment(&self) -> Option<usize> {
+        self.fragment
+    }
+
+    /// The location of the failing line in the Rust source code, if known.
+    pub fn location(&self) -> Option<Location> {
+        self.location
+    }
+}
+
+/// Error returned when invoking a script.
+#[derive(Debug)]
+pub enum Error {
+    /// The Lua runtime raised an error, e.g. by `error()`, a failing `redis.call`
+    /// or an operation on a nil value.
+    Lua(Details),
+    /// The script returned an error reply, e.g. by `redis.error_reply` or `return {err=...}`.
+    ErrorReply(Details),
+    /// The script isn't cached in the server (`NOSCRIPT`).
+    NoScript(Details),
+    /// The server is busy running another script (`BUSY`).
+    Busy(Details),
+    /// The script was killed by `SCRIPT KILL`, e.g. after the deadline.
+    Killed(Details),
+    /// The server is out of memory (`OOM`).
+    Oom(Details),
+    /// The keys don't hash to the same slot in Redis Cluster (`CROSSSLOT`).
+    CrossSlot(Details),
+    /// The connection to the server failed.
+    Connection(Details),
+    /// Any other error such as a type conversion failure.
+    Other(Details),
+}
+
+impl Error {
+    /// Classify the error from `redis-rs` mapping the script lines with `lines`.
+    pub(crate) fn new(raw: redis::RedisError, lines: Option<&LineMap>) -> Self {
+        let kind = Kind::of(&raw);
+        let details = Details::new(raw, &kind, lines);
+
+        match kind {
+            Kind::Lua => Error::Lua(details),
+            Kind::ErrorReply => Error::ErrorReply(details),
+            Kind::NoScript => Error::NoScript(details),
+            Kind::Busy => Error::Busy(details),
+            Kind::Killed => Error::Killed(details),
+            Kind::Oom => Error::Oom(details),
+            Kind::CrossSlot => Error::CrossSlot(details),
+            Kind::Connection => Error::Connection(details),
+            Kind::Other => Error::Other(details),
+        }
+    }
+
+    /// The details of the error.
+    pub fn details(&self) -> &Details {
+        match self {
+            Error::Lua(d)
+            | Error::ErrorReply(d)
+            | Error::NoScript(d)
+            | Error::Busy(d)
+            | Error::Killed(d)
+            | Error::Oom(d)
+            | Error::CrossSlot(d)
+            | Error::Connection(d)
+            | Error::Other(d) => d,
+        }
+    }
+
+    /// The original error from `redis-rs`.
+    pub fn raw(&self) -> &redis::RedisError {
+        self.details().raw()
+    }
+
+    /// Convert into the original error from `redis-rs`.
+    pub fn into_raw(self) -> redis::RedisError {
+        match self {
+            Error::Lua(d)
+            | Error::ErrorReply(d)
+            | Error::NoScript(d)
+            | Error::Busy(d)
+            | Error::Killed(d)
+            | Error::Oom(d)
+            | Error::CrossSlot(d)
+            | Error::Connection(d)
+            | Error::Other(d) => d.raw,
+        }
+    }
+
+    /// The error message without the script name and line number added by Redis.
+    pub fn message(&self) -> &str {
+        self.details().message()
+    }
+
+    /// The index of the joined script which failed, if known.
+    pub fn fragment(&self) -> Option<usize> {
+        self.details().fragment()
+    }
+
+    /// The location of the failing line in the Rust source code, if known.
+    pub fn location(&self) -> Option<Location> {
+        self.details().location()
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.location() {
+            Some(location) => write!(f, "{} (at {})", self.raw(), location),
+            None => write!(f, "{}", self.raw()),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.raw())
+    }
+}
+
+impl From<redis::RedisError> for Error {
+    fn from(err: redis::RedisError) -> Self {
+        Error::new(err, None)
+    }
+}
+
+impl From<Error> for redis::RedisError {
+    fn from(err: Error) -> Self {
+        err.into_raw()
+    }
+}
+
+enum Kind {
+    Lua,
+    ErrorReply,
+    NoScript,
+    Busy,
+    Killed,
+    Oom,
+    CrossSlot,
+    Connection,
+    Other,
+}
+
+impl Kind {
+    fn of(err: &redis::RedisError) -> Self {
+        use redis::ErrorKind;
+
+        if err.is_io_error() || err.is_connection_dropped() || err.is_connection_refusal() {
+            return Kind::Connection;
+        }
+
+        match err.kind() {
+            ErrorKind::NoScriptError => return Kind::NoScript,
+            ErrorKind::CrossSlot => return Kind::CrossSlot,
+            ErrorKind::ResponseError | ErrorKind::ExtensionError => {}
+            _ => return Kind::Other,
+        }
+
+        let detail = err.detail().unwrap_or_default();
+        match err.code() {
+            Some("BUSY") => Kind::Busy,
+            Some("OOM") => Kind::Oom,
+            _ if detail.contains("Script killed by user with SCRIPT KILL") => Kind::Killed,
+            _ if is_lua_error(detail) => Kind::Lua,
+            _ => Kind::ErrorReply,
+        }
+    }
+}
+
+/// Returns `true` if the error is raised by the Lua runtime rather than returned by the script.
+///
+/// Redis prefixes such errors with the script name, e.g.
+/// `Error running script (call to f_...): @user_script:1: ...` before 7.0
+/// or `user_script:1: ... script: ..., on @user_script:1.` since 7.0.
+fn is_lua_error(detail: &str) -> bool {
+    detail.starts_with("Error running script") || script_line(detail).is_some()
+}
+
+/// Strip the script name and the line number which Redis adds to the message.
+fn lua_message(detail: &str) -> String {
+    // Redis 7 appends ` script: <sha>, on @user_script:N.`
+    let detail = match detail.rfind(" script: ") {
+        Some(pos) if detail[pos..].contains("user_script:") => &detail[..pos],
+        _ => detail,
+    };
+
+    // The message follows the last `user_script:N:`
+    let mut message = detail;
+    while let Some(pos) = message.find("user_script:") {
+        // Redis before 7.0 puts a space after the colon for errors of `redis.call`.
+        let rest = message[pos + "user_script:".len()..].trim_start();
+        let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+        match rest[digits..].strip_prefix(':') {
+            Some(rest) if digits > 0 => message = rest,
+            _ => break,
+        }
+    }
+
+    message.trim().to_string()
+}
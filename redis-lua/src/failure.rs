@@ -0,0 +1,162 @@
+use crate::{
+    cluster::Routed,
+    error::Error,
+    options::{Options, WithOptions},
+    script::{Info, Script, ScriptJoin},
+    types::ScriptArg,
+};
+use futures::{future::BoxFuture, prelude::*};
+use serde::de::DeserializeOwned;
+use std::{
+    fmt::{self, Debug, Display},
+    marker::PhantomData,
+};
+
+/// User-defined error which a script raises by `script_error`.
+///
+/// Usually derived by `#[derive(ScriptError)]` on an enum. Each variant is identified by the tag,
+/// which is the variant name in `SCREAMING_SNAKE_CASE`, e.g. `INSUFFICIENT_FUNDS`.
+/// The fields of the variant are given as a Lua table; a table with keys for named fields
+/// and an array for unnamed fields. Tags which are error codes of Redis, e.g. `BUSY` or `ERR`,
+/// are rejected by the derive, as such replies are classified as [`Error`](Error) instead.
+///
+/// ```lua
+/// return script_error("INSUFFICIENT_FUNDS", {balance = balance})
+/// ```
+pub trait ScriptError: Sized {
+    /// Decode the error from the tag and the msgpack-encoded fields.
+    fn decode(tag: &str, fields: &[u8]) -> Option<Self>;
+
+    /// The tag of the variant.
+    fn tag(&self) -> &'static str;
+}
+
+/// Decode the msgpack-encoded fields of a variant.
+#[doc(hidden)]
+pub fn decode_fields<T: DeserializeOwned>(fields: &[u8]) -> Option<T> {
+    rmp_serde::from_slice(fields).ok()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Failure of a script which may raise the user-defined error `E`.
+#[derive(Debug)]
+pub enum ScriptFailure<E> {
+    /// The script raised the user-defined error.
+    Script(E),
+    /// Any other error.
+    Error(Error),
+}
+
+impl<E: ScriptError> From<Error> for ScriptFailure<E> {
+    fn from(err: Error) -> Self {
+        let decoded = match &err {
+            Error::ErrorReply(details) => {
+                // The message is `TAG <hex-encoded msgpack>`
+                let mut parts = details.message().splitn(2, ' ');
+                let tag = parts.next().unwrap_or_default();
+                decode_hex(parts.next().unwrap_or_default())
+                    .and_then(|fields| E::decode(tag, &fields))
+            }
+            _ => None,
+        };
+
+        match decoded {
+            Some(e) => ScriptFailure::Script(e),
+            None => ScriptFailure::Error(err),
+        }
+    }
+}
+
+impl<E: Display> Display for ScriptFailure<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScriptFailure::Script(e) => write!(f, "{}", e),
+            ScriptFailure::Error(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl<E: Debug + Display> std::error::Error for ScriptFailure<E> {}
+
+/// Script which decodes the user-defined error `E` on failure.
+///
+/// `join`, `route` and `with_options` wrap the inner script, so the error is decoded
+/// whichever order they are called in.
+pub struct Raising<S, E>(S, PhantomData<fn() -> E>);
+
+impl<S, E> Raising<S, E> {
+    pub(crate) fn new(script: S) -> Self {
+        Self(script, PhantomData)
+    }
+}
+
+impl<S: Script, E> Raising<S, E> {
+    /// Join another script making self as inner.
+    pub fn join<T: Script>(self, other: T) -> Raising<ScriptJoin<S, T>, E> {
+        Raising::new(self.0.join(other))
+    }
+
+    /// Pass the keys to route the script to the node owning their slot in Redis Cluster.
+    pub fn route<K: redis::ToRedisArgs>(self, keys: &[K]) -> Raising<Routed<S>, E> {
+        Raising::new(self.0.route(keys))
+    }
+
+    /// Invoke the script with the options for long-running scripts, e.g. the deadline.
+    pub fn with_options(self, options: Options) -> Raising<WithOptions<S>, E> {
+        Raising::new(self.0.with_options(options))
+    }
+}
+
+impl<S, E> Raising<S, E>
+where
+    S: Script,
+    E: ScriptError,
+{
+    /// Invoke the script.
+    pub fn invoke<T>(self, con: &mut dyn redis::ConnectionLike) -> Result<T, ScriptFailure<E>>
+    where
+        T: redis::FromRedisValue,
+    {
+        self.0.invoke(con).map_err(ScriptFailure::from)
+    }
+
+    /// Invoke the script asynchronously.
+    pub fn invoke_async<'a, C, T>(
+        self,
+        con: &'a mut C,
+    ) -> BoxFuture<'a, Result<T, ScriptFailure<E>>>
+    where
+        C: redis::aio::ConnectionLike + Send,
+        T: redis::FromRedisValue + Send + 'a,
+        S: Send + 'a,
+        E: Send + 'a,
+    {
+        self.0
+            .invoke_async(con)
+            .map_err(ScriptFailure::from)
+            .boxed()
+    }
+}
+
+impl<S: Script, E> Script for Raising<S, E> {
+    fn info(&self, info: &mut Vec<Info>, args: &mut Vec<ScriptArg>) {
+        self.0.info(info, args);
+    }
+
+    fn route_keys(&self, keys: &mut Vec<Vec<u8>>) {
+        self.0.route_keys(keys);
+    }
+
+    fn invoke_options(&self) -> Option<&Options> {
+        self.0.invoke_options()
+    }
+}
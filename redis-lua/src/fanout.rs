@@ -0,0 +1,240 @@
+use crate::{
+    error::Error,
+    script::{Invocation, Script},
+};
+use std::{
+    any::Any,
+    collections::HashMap,
+    hash::Hash,
+    iter::{FromIterator, Sum},
+    sync::Mutex,
+    thread,
+};
+
+/// Results of a script invoked on each primary node, keyed by the node address `host:port`.
+#[derive(Debug)]
+pub struct NodeResults<T> {
+    results: Vec<(String, Result<T, Error>)>,
+}
+
+impl<T> NodeResults<T> {
+    /// The result of each node.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Result<T, Error>)> {
+        self.results.iter().map(|(node, res)| (node.as_str(), res))
+    }
+
+    /// Convert into the result of each node.
+    pub fn into_inner(self) -> Vec<(String, Result<T, Error>)> {
+        self.results
+    }
+
+    /// The values of all the nodes, or the first error.
+    pub fn values(self) -> Result<Vec<T>, Error> {
+        self.results.into_iter().map(|(_, res)| res).collect()
+    }
+
+    /// Reduce the values of all the nodes by `f`; `None` if there are no nodes.
+    pub fn reduce<F>(self, f: F) -> Result<Option<T>, Error>
+    where
+        F: FnMut(T, T) -> T,
+    {
+        Ok(self.values()?.into_iter().reduce(f))
+    }
+
+    /// The sum of the values, e.g. the number of keys on each node.
+    pub fn sum<S>(self) -> Result<S, Error>
+    where
+        S: Sum<T>,
+    {
+        Ok(self.values()?.into_iter().sum())
+    }
+
+    /// Concatenate the values, e.g. the keys found on each node.
+    pub fn concat<U>(self) -> Result<Vec<U>, Error>
+    where
+        T: IntoIterator<Item = U>,
+    {
+        Ok(self.values()?.into_iter().flatten().collect())
+    }
+
+    /// Merge the maps of the nodes; a later node overwrites the same key.
+    pub fn merge<K, V>(self) -> Result<HashMap<K, V>, Error>
+    where
+        T: IntoIterator<Item = (K, V)>,
+        K: Eq + Hash,
+    {
+        Ok(self.values()?.into_iter().flatten().collect())
+    }
+}
+
+impl<T> FromIterator<(String, Result<T, Error>)> for NodeResults<T> {
+    fn from_iter<I: IntoIterator<Item = (String, Result<T, Error>)>>(iter: I) -> Self {
+        Self {
+            results: iter.into_iter().collect(),
+        }
+    }
+}
+
+fn error(desc: &'static str, detail: String) -> Error {
+    let err = redis::RedisError::from((redis::ErrorKind::ClientError, desc, detail));
+    Error::new(err, None)
+}
+
+fn invalid(detail: String) -> Error {
+    let err = redis::RedisError::from((
+        redis::ErrorKind::TypeError,
+        "Unexpected reply of CLUSTER SLOTS",
+        detail,
+    ));
+    Error::new(err, None)
+}
+
+/// The message of the panic of a thread.
+fn panicked(payload: Box<dyn Any + Send>) -> Error {
+    let detail = payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_default();
+    error("Invocation panicked", detail)
+}
+
+/// The addresses of the primary nodes by `CLUSTER SLOTS`.
+fn primaries(con: &mut dyn redis::ConnectionLike) -> Result<Vec<(String, u16)>, Error> {
+    let slots: Vec<Vec<redis::Value>> = redis::cmd("CLUSTER").arg("SLOTS").query(con)?;
+
+    let mut nodes = vec![];
+    for range in slots {
+        // `[start, end, [host, port, id, ...], replicas...]`
+        let node = match range.get(2) {
+            Some(redis::Value::Bulk(node)) if node.len() >= 2 => node,
+            _ => return Err(invalid(format!("{:?}", range))),
+        };
+        let host: String = redis::from_redis_value(&node[0])?;
+        let port: u16 = redis::from_redis_value(&node[1])?;
+
+        if !nodes.contains(&(host.clone(), port)) {
+            nodes.push((host, port));
+        }
+    }
+    Ok(nodes)
+}
+
+/// Connections to the primary nodes of Redis Cluster, which are reused by
+/// [`Script::invoke_on_all_primaries`](Script::invoke_on_all_primaries).
+///
+/// A connection is used by one invocation at a time; the invocations sharing `Primaries`
+/// on multiple threads wait for each other on each node.
+pub struct Primaries<C = redis::Connection> {
+    nodes: Vec<(String, Mutex<C>)>,
+}
+
+impl Primaries {
+    /// Find the primary nodes by `CLUSTER SLOTS` on `con`, which may be a connection to any node
+    /// or a cluster connection, and connect to each of them once.
+    ///
+    /// Each node is connected by `template` with its address replaced, so that the password
+    /// and the database are shared.
+    pub fn connect(
+        con: &mut dyn redis::ConnectionLike,
+        template: &redis::ConnectionInfo,
+    ) -> Result<Self, Error> {
+        let nodes = primaries(con)?
+            .into_iter()
+            .map(|(host, port)| {
+                let addr = format!("{}:{}", host, port);
+                let mut info = template.clone();
+                info.addr = Box::new(redis::ConnectionAddr::Tcp(host, port));
+                let con = redis::Client::open(info)?.get_connection()?;
+                Ok((addr, con))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(Self::new(nodes))
+    }
+}
+
+impl<C> Primaries<C>
+where
+    C: redis::ConnectionLike + Send,
+{
+    /// Use the connections to the primary nodes, keyed by the node address `host:port`,
+    /// e.g. the ones checked out of a pool.
+    pub fn new(nodes: Vec<(String, C)>) -> Self {
+        Self {
+            nodes: nodes
+                .into_iter()
+                .map(|(addr, con)| (addr, Mutex::new(con)))
+                .collect(),
+        }
+    }
+
+    /// The addresses of the nodes.
+    pub fn nodes(&self) -> impl Iterator<Item = &str> {
+        self.nodes.iter().map(|(addr, _)| addr.as_str())
+    }
+
+    /// Convert into the connections.
+    pub fn into_inner(self) -> Vec<(String, C)> {
+        self.nodes
+            .into_iter()
+            .map(|(addr, con)| {
+                let con = con.into_inner().unwrap_or_else(|e| e.into_inner());
+                (addr, con)
+            })
+            .collect()
+    }
+}
+
+fn invoke_on<C, T>(con: &Mutex<C>, invocation: &Invocation) -> Result<T, Error>
+where
+    C: redis::ConnectionLike,
+    T: redis::FromRedisValue,
+{
+    // A panic while invoking may have left a reply unread on the connection.
+    let mut con = con.lock().map_err(|_| {
+        error(
+            "Connection poisoned",
+            "a previous invocation panicked".into(),
+        )
+    })?;
+    invocation.invoke(&mut *con)
+}
+
+/// Invoke the script on each primary node concurrently, each on its own thread.
+pub(crate) fn invoke_on_all_primaries<S, C, T>(
+    script: &S,
+    primaries: &Primaries<C>,
+) -> Result<NodeResults<T>, Error>
+where
+    S: Script + ?Sized,
+    C: redis::ConnectionLike + Send,
+    T: redis::FromRedisValue + Send,
+{
+    let mut keys = vec![];
+    script.route_keys(&mut keys);
+    if !keys.is_empty() {
+        return Err(error(
+            "Routed script can't be invoked on all primaries",
+            "the keys are owned by a single node".into(),
+        ));
+    }
+    let invocation = Invocation::new(script)?;
+    let invocation = &invocation;
+
+    Ok(thread::scope(|s| {
+        let handles: Vec<_> = primaries
+            .nodes
+            .iter()
+            .map(|(addr, con)| {
+                let handle = s.spawn(move || invoke_on(con, invocation));
+                (addr.clone(), handle)
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|(addr, handle)| (addr, handle.join().unwrap_or_else(|e| Err(panicked(e)))))
+            .collect()
+    }))
+}
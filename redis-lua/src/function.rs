@@ -0,0 +1,134 @@
+use crate::{cluster::check_slot, error::Error, gen::add_args, types::ScriptArg};
+
+/// Function library generated by `lua_library!`.
+#[derive(Debug)]
+pub struct Library {
+    name: &'static str,
+    code: &'static str,
+}
+
+impl Library {
+    #[doc(hidden)]
+    pub const fn new(name: &'static str, code: &'static str) -> Self {
+        Self { name, code }
+    }
+
+    /// The name of the library.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The source of the library starting with `#!lua name=...`.
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+
+    fn load_cmd(&self, replace: bool) -> redis::Cmd {
+        let mut cmd = redis::cmd("FUNCTION");
+        cmd.arg("LOAD");
+        if replace {
+            cmd.arg("REPLACE");
+        }
+        cmd.arg(self.code);
+        cmd
+    }
+
+    /// Load the library by `FUNCTION LOAD`, replacing the existing one if `replace` is `true`.
+    pub fn load(&self, con: &mut dyn redis::ConnectionLike, replace: bool) -> Result<(), Error> {
+        self.load_cmd(replace).query::<String>(con)?;
+        Ok(())
+    }
+
+    /// Load the library by `FUNCTION LOAD` asynchronously.
+    pub async fn load_async<C>(&self, con: &mut C, replace: bool) -> Result<(), Error>
+    where
+        C: redis::aio::ConnectionLike + Send,
+    {
+        self.load_cmd(replace).query_async::<_, String>(con).await?;
+        Ok(())
+    }
+}
+
+/// Call of a function in a library with its arguments.
+pub struct FunctionCall {
+    name: &'static str,
+    keys: Vec<Vec<u8>>,
+    args: Vec<ScriptArg>,
+}
+
+impl FunctionCall {
+    #[doc(hidden)]
+    pub fn new(name: &'static str, args: Vec<ScriptArg>) -> Self {
+        Self {
+            name,
+            keys: vec![],
+            args,
+        }
+    }
+
+    /// Pass the keys to route the call to the node owning their slot in Redis Cluster.
+    ///
+    /// The function receives them as the first parameter of the callback, `keys`.
+    pub fn route<K>(mut self, keys: &[K]) -> Self
+    where
+        K: redis::ToRedisArgs,
+    {
+        self.keys
+            .extend(keys.iter().flat_map(|k| k.to_redis_args()));
+        self
+    }
+
+    /// The hash slot of the keys; an error if they span multiple slots.
+    pub fn slot(&self) -> Result<Option<u16>, Error> {
+        check_slot(&self.keys)
+    }
+
+    fn cmd(&self, command: &str) -> Result<redis::Cmd, Error> {
+        self.slot()?;
+
+        let mut cmd = redis::cmd(command);
+        cmd.arg(self.name).arg(self.keys.len()).arg(&self.keys[..]);
+        add_args(&mut cmd, &self.args);
+        Ok(cmd)
+    }
+
+    /// Call the function by `FCALL`.
+    pub fn fcall<T>(&self, con: &mut dyn redis::ConnectionLike) -> Result<T, Error>
+    where
+        T: redis::FromRedisValue,
+    {
+        self.cmd("FCALL")?.query(con).map_err(Error::from)
+    }
+
+    /// Call the function declared by `#[no_writes]` by `FCALL_RO`, which is allowed on replicas.
+    pub fn fcall_ro<T>(&self, con: &mut dyn redis::ConnectionLike) -> Result<T, Error>
+    where
+        T: redis::FromRedisValue,
+    {
+        self.cmd("FCALL_RO")?.query(con).map_err(Error::from)
+    }
+
+    /// Call the function by `FCALL` asynchronously.
+    pub async fn fcall_async<C, T>(&self, con: &mut C) -> Result<T, Error>
+    where
+        C: redis::aio::ConnectionLike + Send,
+        T: redis::FromRedisValue + Send,
+    {
+        self.cmd("FCALL")?
+            .query_async(con)
+            .await
+            .map_err(Error::from)
+    }
+
+    /// Call the function by `FCALL_RO` asynchronously.
+    pub async fn fcall_ro_async<C, T>(&self, con: &mut C) -> Result<T, Error>
+    where
+        C: redis::aio::ConnectionLike + Send,
+        T: redis::FromRedisValue + Send,
+    {
+        self.cmd("FCALL_RO")?
+            .query_async(con)
+            .await
+            .map_err(Error::from)
+    }
+}
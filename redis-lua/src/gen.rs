@@ -0,0 +1,374 @@
+use crate::{error::Error, lines::LineMap, script::Info, types::ScriptArg};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, OnceLock, RwLock},
+};
+
+// The script text generated here must match `redis_lua_macro::gen`,
+// which generates the same text for scripts which are not joined at compile time.
+
+/// Helpers at the top of every script, which are shared with `redis_lua_macro::gen` in
+/// `redis-lua-macro/src/prelude` and must fit in a single line to keep the line numbers.
+///
+/// * `script_error` returns a user-defined error as `TAG <hex-encoded msgpack of fields>`.
+/// * `__internal_arg(i)` returns `ARGV[i]`, which is unpacked if the flag of the argument is `1`.
+///   The flags are given as the last argument so that the script text is independent of the values.
+#[cfg(not(feature = "minify"))]
+const PRELUDE: &str = concat!(
+    include_str!("../../redis-lua-macro/src/prelude/script_error.lua"),
+    " ",
+    include_str!("../../redis-lua-macro/src/prelude/args.lua"),
+    " "
+);
+
+#[cfg(feature = "minify")]
+const PRELUDE: &str = concat!(
+    include_str!("../../redis-lua-macro/src/prelude/script_error.min.lua"),
+    " ",
+    include_str!("../../redis-lua-macro/src/prelude/args.min.lua"),
+    " "
+);
+
+/// The helper function to get the argument.
+#[cfg(not(feature = "minify"))]
+const ARG: &str = "__internal_arg";
+#[cfg(feature = "minify")]
+const ARG: &str = "__g";
+
+/// Generate the initialization of the arguments which start from `ARGV[first]`.
+#[cfg(not(feature = "minify"))]
+fn prologue(info: &Info, first: usize) -> String {
+    let values = (first..first + info.args().len()).map(|i| format!("{}({})", ARG, i));
+
+    if info.table_layout() {
+        let table: Vec<_> = values.map(|v| format!("({})", v)).collect();
+        format!("local {} = {{{}}} ", info.arg_table(), table.join(", "))
+    } else {
+        info.args()
+            .iter()
+            .zip(values)
+            .map(|(arg, value)| format!("local {} = {} ", arg, value))
+            .collect()
+    }
+}
+
+/// Generate the initialization of the arguments which start from `ARGV[first]`.
+#[cfg(feature = "minify")]
+fn prologue(info: &Info, first: usize) -> String {
+    if info.args().is_empty() {
+        return String::new();
+    }
+
+    let values = (first..first + info.args().len()).map(|i| format!("{}({})", ARG, i));
+
+    if info.table_layout() {
+        let table: Vec<_> = values.map(|v| format!("({})", v)).collect();
+        format!("local {}={{{}}} ", info.arg_table(), table.join(","))
+    } else {
+        let values: Vec<_> = values.collect();
+        format!("local {}={} ", info.args().join(","), values.join(","))
+    }
+}
+
+/// Wrap the script in a function to isolate local variables.
+#[cfg(not(feature = "minify"))]
+fn wrap(init: &str, body: &str, last: bool) -> String {
+    if last {
+        format!("return (function() {} {} end)();\n", init, body)
+    } else {
+        // Error replies of the inner scripts, e.g. by `script_error`, are returned immediately.
+        format!(
+            "do local __internal_ret = (function() {} {} end)(); \
+             if type(__internal_ret) == 'table' and __internal_ret.err then \
+             return __internal_ret end end\n",
+            init, body
+        )
+    }
+}
+
+/// Wrap the script in a function to isolate local variables.
+#[cfg(feature = "minify")]
+fn wrap(init: &str, body: &str, last: bool) -> String {
+    if last {
+        format!("return(function(){}{} end)()\n", init, body)
+    } else {
+        // Error replies of the inner scripts, e.g. by `script_error`, are returned immediately.
+        format!(
+            "do local __r=(function(){}{} end)()\
+             if type(__r)=='table'and __r.err then return __r end end\n",
+            init, body
+        )
+    }
+}
+
+/// Add the arguments followed by their pack flags, e.g. `"01"` if the second one is packed.
+pub(crate) fn add_args(cmd: &mut redis::Cmd, args: &[ScriptArg]) {
+    let flags: String = args
+        .iter()
+        .map(|arg| if arg.pack() { '1' } else { '0' })
+        .collect();
+
+    cmd.arg(args).arg(flags);
+}
+
+/// Joined scripts generated at runtime.
+pub(crate) struct Joined {
+    code: String,
+    hash: String,
+    lines: LineMap,
+    killable: bool,
+}
+
+impl Joined {
+    fn new(info: &[Info]) -> Self {
+        let mut code = String::from(PRELUDE);
+        let mut lines = LineMap::new();
+        let mut first = 1;
+        let last = info.len() - 1;
+
+        for (index, info) in info.iter().enumerate() {
+            let init = prologue(info, first);
+            first += info.args().len();
+
+            // Too many arguments to hold in local variables; pass them in a single table.
+            let body = if info.table_layout() {
+                info.table()
+            } else {
+                info.body()
+            };
+
+            // The body starts at the same line as the prologue.
+            lines.add(code.matches('\n').count() + 1, info.location());
+
+            code += &wrap(&init, body, index == last);
+        }
+
+        let hash = redis::Script::new(&code).get_hash().to_string();
+        let killable = info.iter().all(Info::killable);
+
+        Self {
+            code,
+            hash,
+            lines,
+            killable,
+        }
+    }
+}
+
+/// Identity of the script information.
+///
+/// The script information of the same `lua!` shares the same static data, so the
+/// addresses identify it without hashing the script text. The text doesn't depend
+/// on the arguments, so the key doesn't include how they are packed.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Key {
+    code: usize,
+    file: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Key {
+    fn new(info: &Info) -> Self {
+        let location = info.location();
+        Self {
+            code: info.code().as_ptr() as usize,
+            file: location.file().as_ptr() as usize,
+            line: location.line(),
+            column: location.column(),
+        }
+    }
+}
+
+/// The maximum number of joined scripts kept in the cache.
+const MAX_JOINED: usize = 1024;
+
+/// Cache of the joined scripts; the oldest ones are evicted first.
+///
+/// Joins built at the same call sites have the same keys, so the cache stays small
+/// unless the chains are built dynamically, e.g. by folding a variable number of scripts.
+#[derive(Default)]
+struct JoinedCache {
+    scripts: HashMap<Vec<Key>, Arc<Joined>>,
+    order: VecDeque<Vec<Key>>,
+}
+
+impl JoinedCache {
+    fn insert(&mut self, key: Vec<Key>, info: &[Info]) -> Arc<Joined> {
+        if let Some(joined) = self.scripts.get(&key) {
+            return joined.clone();
+        }
+
+        if self.order.len() >= MAX_JOINED {
+            if let Some(oldest) = self.order.pop_front() {
+                self.scripts.remove(&oldest);
+            }
+        }
+
+        let joined = Arc::new(Joined::new(info));
+        self.order.push_back(key.clone());
+        self.scripts.insert(key, joined.clone());
+        joined
+    }
+}
+
+/// Script ready to be invoked.
+pub(crate) enum Generated<'a> {
+    /// A single script, which is generated at compile time.
+    Single(&'a Info),
+    /// Joined scripts, which are generated once and reused.
+    Joined(Arc<Joined>),
+}
+
+impl<'a> Generated<'a> {
+    /// The script of the list of script information.
+    pub(crate) fn get(info: &'a [Info]) -> Self {
+        assert!(!info.is_empty(), "No script information");
+
+        if let [info] = info {
+            return Generated::Single(info);
+        }
+
+        static JOINED: OnceLock<RwLock<JoinedCache>> = OnceLock::new();
+
+        let cache = JOINED.get_or_init(Default::default);
+        let key: Vec<_> = info.iter().map(Key::new).collect();
+
+        if let Some(joined) = cache.read().unwrap().scripts.get(&key) {
+            return Generated::Joined(joined.clone());
+        }
+
+        Generated::Joined(cache.write().unwrap().insert(key, info))
+    }
+
+    fn code(&self) -> &str {
+        match self {
+            Generated::Single(info) => info.code(),
+            Generated::Joined(joined) => &joined.code,
+        }
+    }
+
+    pub(crate) fn hash(&self) -> &str {
+        match self {
+            Generated::Single(info) => info.hash(),
+            Generated::Joined(joined) => &joined.hash,
+        }
+    }
+
+    /// Returns `true` if all the scripts can be killed by `SCRIPT KILL`.
+    pub(crate) fn killable(&self) -> bool {
+        match self {
+            Generated::Single(info) => info.killable(),
+            Generated::Joined(joined) => joined.killable,
+        }
+    }
+
+    /// Build `EVALSHA` or `EVAL` with the keys and the arguments followed by their pack flags.
+    fn eval(
+        &self,
+        command: &str,
+        script: &str,
+        keys: &[Vec<u8>],
+        args: &[ScriptArg],
+    ) -> redis::Cmd {
+        let mut cmd = redis::cmd(command);
+        cmd.arg(script).arg(keys.len()).arg(keys);
+        add_args(&mut cmd, args);
+        cmd
+    }
+
+    /// Build the `EVALSHA` command with the keys and the arguments followed by their pack flags.
+    pub(crate) fn evalsha(&self, keys: &[Vec<u8>], args: &[ScriptArg]) -> redis::Cmd {
+        self.eval("EVALSHA", self.hash(), keys, args)
+    }
+
+    /// Build the `EVAL` command, which sends the code and caches the script in the server.
+    pub(crate) fn eval_code(&self, keys: &[Vec<u8>], args: &[ScriptArg]) -> redis::Cmd {
+        self.eval("EVAL", self.code(), keys, args)
+    }
+
+    pub(crate) fn load(&self) -> redis::Cmd {
+        let mut cmd = redis::cmd("SCRIPT");
+        cmd.arg("LOAD").arg(self.code());
+        cmd
+    }
+
+    pub(crate) fn error(&self, err: redis::RedisError) -> Error {
+        match self {
+            Generated::Single(info) => {
+                let mut lines = LineMap::new();
+                lines.add(1, info.location());
+                Error::new(err, Some(&lines))
+            }
+            Generated::Joined(joined) => Error::new(err, Some(&joined.lines)),
+        }
+    }
+
+    /// Invoke the script, loading it if it's not cached in the server.
+    ///
+    /// `SCRIPT LOAD` may go to another node in Redis Cluster, so the script is sent
+    /// by `EVAL` instead if there are keys, which is routed to the same node as `EVALSHA`.
+    pub(crate) fn invoke<T>(
+        &self,
+        con: &mut dyn redis::ConnectionLike,
+        keys: &[Vec<u8>],
+        args: &[ScriptArg],
+    ) -> Result<T, Error>
+    where
+        T: redis::FromRedisValue,
+    {
+        let cmd = self.eval("EVALSHA", self.hash(), keys, args);
+        match cmd.query(con) {
+            Err(e) if e.kind() == redis::ErrorKind::NoScriptError && keys.is_empty() => {
+                self.load()
+                    .query::<String>(con)
+                    .map_err(|e| self.error(e))?;
+                cmd.query(con).map_err(|e| self.error(e))
+            }
+            Err(e) if e.kind() == redis::ErrorKind::NoScriptError => self
+                .eval_code(keys, args)
+                .query(con)
+                .map_err(|e| self.error(e)),
+            r => r.map_err(|e| self.error(e)),
+        }
+    }
+
+    /// Invoke the script asynchronously, loading it if it's not cached in the server.
+    pub(crate) async fn invoke_async<C, T>(
+        &self,
+        con: &mut C,
+        keys: &[Vec<u8>],
+        args: &[ScriptArg],
+    ) -> Result<T, Error>
+    where
+        C: redis::aio::ConnectionLike + Send,
+        T: redis::FromRedisValue + Send,
+    {
+        let cmd = self.eval("EVALSHA", self.hash(), keys, args);
+        match cmd.query_async(con).await {
+            Err(e) if e.kind() == redis::ErrorKind::NoScriptError && keys.is_empty() => {
+                self.load()
+                    .query_async::<_, String>(con)
+                    .await
+                    .map_err(|e| self.error(e))?;
+                cmd.query_async(con).await.map_err(|e| self.error(e))
+            }
+            Err(e) if e.kind() == redis::ErrorKind::NoScriptError => self
+                .eval_code(keys, args)
+                .query_async(con)
+                .await
+                .map_err(|e| self.error(e)),
+            r => r.map_err(|e| self.error(e)),
+        }
+    }
+}
+
+/// Generate a script from a list of script information.
+///
+/// The script takes the arguments followed by a string of their pack flags,
+/// e.g. `"01"` if the second argument is packed by msgpack.
+/// Unlike `invoke`, the script is always generated, even for a single script.
+pub fn gen_script(info: &[Info]) -> redis::Script {
+    assert!(!info.is_empty(), "No script information");
+    redis::Script::new(&Joined::new(info).code)
+}
@@ -25,7 +25,11 @@
 //! # }
 //! ```
 //!
-//! Any Lua syntax supported by Redis Lua is usable.
+//! Any Lua syntax supported by Redis Lua is usable. Redis embeds Lua 5.1, so the syntax of later versions
+//! such as `goto` or bitwise operators is rejected at compile time.
+//!
+//! Note that `//` starts a Rust comment inside `lua!`, so the rest of the line is dropped
+//! before the macro sees it. Use `math.floor(a / b)` for the integer division of Lua 5.3.
 //!
 //! * if-else
 //!
@@ -151,6 +155,10 @@
 //!
 //! The script object is clonable if all the variables it captures are clonable or it captures no variables.
 //!
+//! The names of `$` variables become methods of the script object, so the names of its methods such as
+//! `invoke`, `join` or `clone` are rejected. Lua keywords such as `end` are also rejected unless written
+//! as raw identifiers, e.g. `$r#end`.
+//!
 //! # Type conversion
 //!
 //! `@` and `$` allow to pass Rust variables to Lua scripts. Primitive types and strings are converted to
@@ -161,18 +169,292 @@
 //! Complicated types such as structs, tuples, maps and non-u8 vectors are converted to Lua tables.
 //! The name of struct members become the key of tables.
 //!
-//! # Limitation
+//! # Errors
+//!
+//! When a script fails at runtime, Redis reports the line of the generated script, e.g. `user_script:3`.
+//! The error returned by `invoke` additionally contains the corresponding location in the Rust source code,
+//! e.g. `(at src/main.rs:12)`.
+//!
+//! The error is [`Error`](Error), which tells a Lua runtime error from an error reply returned by the script,
+//! `NOSCRIPT`, `BUSY`, a script killed by `SCRIPT KILL`, `OOM`, `CROSSSLOT` and connection failures.
+//! Each variant keeps the original [`redis::RedisError`][], the Lua error message, the index of the failing
+//! script in a joined script and the location in Rust.
+//!
+//! ```rust
+//! # use redis_lua::{lua, Error};
+//! #
+//! # fn main() {
+//! # let mut cli = redis::Client::open("redis://localhost").unwrap();
+//! #
+//! let script = lua!(return redis.error_reply("MYERR failed"));
+//! match script.invoke::<()>(&mut cli) {
+//!     Err(Error::ErrorReply(e)) => assert_eq!(e.message(), "MYERR failed"),
+//!     _ => panic!(),
+//! }
+//! # }
+//! ```
+//!
+//! # User-defined errors
+//!
+//! Scripts can return business-level errors which decode into Rust enums. Derive [`ScriptError`](ScriptError)
+//! for an enum and return its variant by `script_error` in the script. The first argument is the variant name
+//! in `SCREAMING_SNAKE_CASE` and the second is the table of fields. The fields are carried as msgpack.
+//! The error returned by an inner script of joined scripts is also returned immediately.
+//!
+//! [`Script::raising`](Script::raising) makes `invoke` return [`ScriptFailure`](ScriptFailure).
+//!
+//! ```rust
+//! # use redis_lua::{lua, Script, ScriptError, ScriptFailure};
+//! #
+//! # fn main() {
+//! # let mut cli = redis::Client::open("redis://localhost").unwrap();
+//! #
+//! #[derive(Debug, PartialEq, ScriptError)]
+//! enum TransferError {
+//!     InsufficientFunds { balance: i64 },
+//!     AccountLocked,
+//! }
+//!
+//! let script = lua! {
+//!     return script_error("INSUFFICIENT_FUNDS", {balance = 10})
+//! };
+//! match script.raising::<TransferError>().invoke::<()>(&mut cli) {
+//!     Err(ScriptFailure::Script(e)) => {
+//!         assert_eq!(e, TransferError::InsufficientFunds { balance: 10 })
+//!     }
+//!     _ => panic!(),
+//! }
+//! # }
+//! ```
+//!
+//! # Pipeline
+//!
+//! [`ScriptPipeline`](ScriptPipeline) sends scripts and other commands in a single round trip.
+//! The scripts which aren't cached in the server are loaded by `SCRIPT LOAD` before the pipeline is sent.
+//!
+//! ```rust
+//! # use redis_lua::{lua, ScriptPipeline};
+//! #
+//! # fn main() {
+//! # let mut cli = redis::Client::open("redis://localhost").unwrap();
+//! #
+//! let (a, b): (usize, usize) = ScriptPipeline::new()
+//!     .cmd(redis::cmd("SET").arg("doc_pipeline").arg(1).clone())
+//!     .ignore()
+//!     .script(&lua!(return redis.call("GET", "doc_pipeline") + 1))
+//!     .script(&lua!(return $x * 2).x(3))
+//!     .query(&mut cli)
+//!     .unwrap();
+//! assert_eq!((a, b), (2, 6));
+//! # }
+//! ```
+//!
+//! # Transaction
+//!
+//! [`transaction`](transaction) runs scripts and commands atomically by `MULTI`/`EXEC`. The keys are watched
+//! by `WATCH` and the transaction is retried if they are modified by others before `EXEC`.
+//!
+//! ```rust
+//! # use redis_lua::{lua, transaction};
+//! #
+//! # fn main() {
+//! # let cli = redis::Client::open("redis://localhost").unwrap();
+//! let mut con = cli.get_connection().unwrap();
+//!
+//! let key = "doc_transaction";
+//! let (count,): (usize,) = transaction(&mut con, &[key], |con, pipe| {
+//!     let current: Option<usize> = redis::cmd("GET").arg(key).query(con)?;
+//!     pipe.cmd(redis::cmd("SET").arg(key).arg(current.unwrap_or(0) + 1).clone())
+//!         .ignore()
+//!         .script(&lua!(return redis.call("GET", @key) * 2));
+//!     Ok(())
+//! })
+//! .unwrap();
+//! assert_eq!(count % 2, 0);
+//! # }
+//! ```
+//!
+//! # Script inventory
+//!
+//! Every `lua!` script is registered at compile time. [`scripts`](scripts) lists them with a unique name
+//! made of the module path and the location, and the SHA1 hash. [`preload_scripts`](preload_scripts) loads them by `SCRIPT LOAD`, e.g. at
+//! startup, and [`check_scripts`](check_scripts) returns the ones not cached in the server by `SCRIPT EXISTS`.
+//! Scripts joined at runtime are not registered.
+//!
+//! ```rust
+//! # use redis_lua::{check_scripts, lua, preload_scripts, scripts};
+//! #
+//! # fn main() {
+//! # let mut cli = redis::Client::open("redis://localhost").unwrap();
+//! #
+//! # let _ = lua!(return 1);
+//! for s in scripts() {
+//!     println!("{}: {}", s.name(), s.hash());
+//! }
+//!
+//! preload_scripts(&mut cli).unwrap();
+//! assert!(check_scripts(&mut cli).unwrap().is_empty());
+//! # }
+//! ```
+//!
+//! # Functions
+//!
+//! [`lua_library`][] defines a library of [Redis functions](https://redis.io/docs/manual/programmability/functions-intro/)
+//! available since Redis 7. It generates a module named after the library, which has `LIBRARY` to load
+//! the library by `FUNCTION LOAD` and a caller of each function. The parameters are given by `$` variables
+//! listed after the function name; `@` captures are not allowed. Functions declared by `#[no_writes]`
+//! can be called by `FCALL_RO`. The keys given by [`FunctionCall::route`](FunctionCall::route) are passed
+//! to the function as `keys`, so that the call is routed in Redis Cluster as `Script::route` does.
+//!
+//! ```rust,ignore
+//! use redis_lua::lua_library;
+//!
+//! lua_library! {
+//!     name = "billing";
+//!
+//!     fn charge(account, amount) {
+//!         return redis.call("DECRBY", $account, $amount)
+//!     }
+//!
+//!     fn deposit(amount) {
+//!         return redis.call("INCRBY", keys[1], $amount)
+//!     }
+//!
+//!     #[no_writes]
+//!     fn balance(account) {
+//!         return tonumber(redis.call("GET", $account))
+//!     }
+//! }
+//!
+//! # fn main() {
+//! # let mut cli = redis::Client::open("redis://localhost").unwrap();
+//! #
+//! billing::LIBRARY.load(&mut cli, true).unwrap();
+//!
+//! let rest: i64 = billing::charge("alice", 10).fcall(&mut cli).unwrap();
+//! let balance: i64 = billing::balance("alice").fcall_ro(&mut cli).unwrap();
+//! assert_eq!(rest, balance);
+//!
+//! let balance: i64 = billing::deposit(10).route(&["bob"]).fcall(&mut cli).unwrap();
+//! # }
+//! ```
+//!
+//! # Redis Cluster
+//!
+//! [`Script::route`](Script::route) gives the keys of the script, so that it's routed to the node owning their
+//! hash slot, e.g. by `redis::cluster::ClusterConnection`. The keys must hash to the same slot respecting
+//! `{hashtags}`; otherwise [`Error::CrossSlot`](Error::CrossSlot) is returned without sending the script.
+//! The keys are kept through the other wrappers such as `with_options` and in [`ScriptPipeline`](ScriptPipeline).
+//!
+//! Pipelines and [`transaction`](transaction) aren't routed by the keys; they're sent to the node
+//! of the given connection as a whole, and `redis::cluster::ClusterConnection` refuses pipelines.
+//! Connect to the node owning the slot of the keys to run them in Redis Cluster.
+//!
+//! ```rust
+//! # use redis_lua::{lua, Error, Script};
+//! #
+//! # fn main() {
+//! # let mut cli = redis::Client::open("redis://localhost").unwrap();
+//! #
+//! let script = lua!(return redis.call("GET", $key));
+//!
+//! let key = "{user1}.name";
+//! let _: Option<String> = script.clone().key(key).route(&[key]).invoke(&mut cli).unwrap();
 //!
-//! * The comment `--` is available only in nightly.
-//! * The warnings are available only in nightly. All the warnings are treated as errors in stable.
+//! let err = script.key("a").route(&["a", "b"]).invoke::<()>(&mut cli).unwrap_err();
+//! assert!(matches!(err, Error::CrossSlot(_)));
+//! # }
+//! ```
+//!
+//! [`Script::invoke_on_all_primaries`](Script::invoke_on_all_primaries) runs a script on every primary node
+//! concurrently, e.g. for maintenance. The connections to the nodes are held by [`Primaries`](Primaries),
+//! which is built once and reused. The results of the nodes can be reduced by [`NodeResults`](NodeResults).
+//!
+//! ```rust,ignore
+//! # use redis::IntoConnectionInfo;
+//! # use redis_lua::{lua, Primaries, Script};
+//! #
+//! # fn main() {
+//! let info = "redis://127.0.0.1:7000".into_connection_info().unwrap();
+//! let mut con = redis::Client::open(info.clone()).unwrap().get_connection().unwrap();
+//! let primaries = Primaries::connect(&mut con, &info).unwrap();
+//!
+//! let keys: usize = lua!(return redis.call("DBSIZE"))
+//!     .invoke_on_all_primaries::<_, usize>(&primaries)
+//!     .unwrap()
+//!     .sum()
+//!     .unwrap();
+//! # }
+//! ```
+//!
+//! # Long-running scripts
+//!
+//! While a script runs past `busy-reply-threshold`, other clients get `BUSY`.
+//! [`Script::with_options`](Script::with_options) invokes the script with [`Options`](Options):
+//! a deadline after which `SCRIPT KILL` is sent on a separate connection, and retries with backoff on `BUSY`.
+//! Only the scripts calling read-only commands are killed, as Redis refuses to kill a script once it has
+//! written; [`Info::killable`](Info::killable) tells whether the script is. The deadlines and the backoffs of
+//! `invoke_async` share a single timer thread, so no thread is spawned per invocation.
+//!
+//! ```rust
+//! # use redis::IntoConnectionInfo;
+//! # use redis_lua::{lua, Options, Script};
+//! # use std::time::Duration;
+//! #
+//! # fn main() {
+//! # let mut cli = redis::Client::open("redis://localhost").unwrap();
+//! #
+//! let options = Options::new()
+//!     .deadline(Duration::from_secs(10), "redis://localhost".into_connection_info().unwrap())
+//!     .busy_retries(3)
+//!     .backoff(Duration::from_millis(100), Duration::from_secs(1));
+//!
+//! let num: usize = lua!(return 1).with_options(options).invoke(&mut cli).unwrap();
+//! assert_eq!(num, 1);
+//! # }
+//! ```
+//!
+//! # Minification
+//!
+//! With `minify` feature, comments and redundant whitespaces are removed from the scripts, the internal
+//! variables get shorter names and the arguments are initialized in a single statement. Line breaks are kept,
+//! so the errors still point to the right lines in the Rust source code.
+//!
+//! # Lint cache
+//!
+//! The lint results are cached in `redis-lua-lint` under `OUT_DIR` or the target directory,
+//! so unchanged scripts are not linted again on incremental builds. The location can be changed
+//! by `REDIS_LUA_CACHE_DIR`; setting it to an empty string disables the cache. Results not used
+//! for 30 days are removed.
+//!
+//! # Warnings
+//!
+//! Lints such as unused variables are reported as compiler warnings on both stable and nightly.
+//! They appear as the use of a deprecated item `LuaWarning` carrying the message, and can be silenced
+//! by `#[allow(deprecated)]`.
 //!
 
 use proc_macro_hack::proc_macro_hack;
 
+mod cluster;
+mod error;
+mod failure;
+mod fanout;
+mod function;
+mod gen;
+mod lines;
+#[cfg(doctest)]
+mod lint_tests;
+mod options;
+mod pipeline;
+mod registry;
 mod script;
+mod transaction;
 mod types;
 
 pub use futures;
+#[doc(hidden)]
+pub use inventory;
 pub use redis;
 pub use serde;
 
@@ -184,6 +466,25 @@ pub use redis_lua_macro::lua;
 #[proc_macro_hack]
 pub use redis_lua_macro::lua_s;
 
-pub use script::{gen_script, Info, Script, ScriptJoin, TakeScript};
+/// Macro to define a library of Redis functions.
+pub use redis_lua_macro::lua_library;
+
+/// Derive macro to decode user-defined errors raised by scripts.
+pub use redis_lua_macro::ScriptError;
+
+pub use cluster::{slot, Routed};
+pub use error::{Details, Error};
+pub use failure::{decode_fields, Raising, ScriptError, ScriptFailure};
+pub use fanout::{NodeResults, Primaries};
+pub use function::{FunctionCall, Library};
+pub use gen::gen_script;
+pub use lines::{LineMap, Location};
+pub use options::{Options, WithOptions};
+pub use pipeline::ScriptPipeline;
+pub use registry::{
+    check_scripts, check_scripts_async, preload_scripts, preload_scripts_async, scripts, Registered,
+};
+pub use script::{Info, Script, ScriptJoin, TakeScript};
+pub use transaction::{transaction, transaction_async};
 
 pub use types::{script_arg, ScriptArg};
@@ -0,0 +1,83 @@
+use std::fmt::{self, Display};
+
+/// Location in the Rust source code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Location {
+    file: &'static str,
+    line: usize,
+    column: usize,
+}
+
+impl Location {
+    /// Create the new location.
+    pub const fn new(file: &'static str, line: usize, column: usize) -> Self {
+        Self { file, line, column }
+    }
+
+    /// The path of the source file.
+    pub fn file(&self) -> &'static str {
+        self.file
+    }
+
+    /// The line number (1-indexed).
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// The column number (1-indexed).
+    pub fn column(&self) -> usize {
+        self.column
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)
+    }
+}
+
+/// Maps the lines of a generated script to the Rust source code.
+#[derive(Clone, Debug, Default)]
+pub struct LineMap {
+    /// The first line of each fragment in the generated script, and its location.
+    fragments: Vec<(usize, Location)>,
+}
+
+impl LineMap {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a fragment which starts at `line` of the generated script.
+    pub(crate) fn add(&mut self, line: usize, location: Location) {
+        self.fragments.push((line, location));
+    }
+
+    /// The index of the fragment containing the line of the generated script.
+    pub fn fragment(&self, line: usize) -> Option<usize> {
+        self.fragments.iter().rposition(|(start, _)| *start <= line)
+    }
+
+    /// Convert the line of the generated script to the location in the Rust source code.
+    pub fn locate(&self, line: usize) -> Option<Location> {
+        let (start, location) = self.fragments[self.fragment(line)?];
+        let offset = line - start;
+
+        Some(Location {
+            file: location.file,
+            line: location.line + offset,
+            column: if offset == 0 { location.column } else { 1 },
+        })
+    }
+}
+
+/// Find the line number of `user_script:N` in the error message from Redis.
+pub fn script_line(msg: &str) -> Option<usize> {
+    let pattern = "user_script:";
+    let pos = msg.find(pattern)? + pattern.len();
+    let digits: String = msg[pos..]
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    digits.parse().ok()
+}
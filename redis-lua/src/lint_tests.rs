@@ -0,0 +1,164 @@
+//! Compile-fail tests of the lints run by `lua!` and `lua_library!`
+//! and the checks of `#[derive(ScriptError)]`.
+//!
+//! Each lint has a failing case next to a passing one, so that a failure
+//! for an unrelated reason doesn't go unnoticed. Warnings are denied
+//! by `deny(deprecated)` where the lint is a warning.
+
+/// Syntax of Lua 5.2 or later.
+///
+/// ```compile_fail
+/// # use redis_lua::lua;
+/// let _ = lua!(for i = 1, 3 do goto done end ::done::);
+/// ```
+///
+/// ```compile_fail
+/// # use redis_lua::lua;
+/// let _ = lua!(return 6 & 3);
+/// ```
+///
+/// ```
+/// # use redis_lua::lua;
+/// let _ = lua!(return math.floor(6 / 4));
+/// ```
+pub struct UnsupportedSyntax;
+
+/// Rust operators and literals which Lua doesn't have.
+///
+/// ```compile_fail
+/// # use redis_lua::lua;
+/// let _ = lua!(return 1 != 2);
+/// ```
+///
+/// ```compile_fail
+/// # use redis_lua::lua;
+/// let _ = lua!(return 0x1_0000_0000_0000_0000_0000_0000_0000_0000);
+/// ```
+///
+/// ```
+/// # use redis_lua::lua;
+/// let _ = lua!(return not (1 == 2), 0x10);
+/// ```
+pub struct RustSyntax;
+
+/// Heuristics for Rust habits are warnings, since the code may be valid Lua.
+///
+/// ```compile_fail
+/// #![deny(deprecated)]
+/// # use redis_lua::lua;
+/// let _ = lua!(local t = {} return t.len());
+/// ```
+///
+/// ```
+/// # use redis_lua::lua;
+/// let _ = lua!(local t = {len = function() return 1 end} return t.len());
+/// ```
+pub struct RustHabits;
+
+/// Parse errors.
+///
+/// ```compile_fail
+/// # use redis_lua::lua;
+/// let _ = lua!(if true then return 1);
+/// ```
+///
+/// ```
+/// # use redis_lua::lua;
+/// let _ = lua!(if true then return 1 end);
+/// ```
+pub struct ParseError;
+
+/// Global variables.
+///
+/// ```compile_fail
+/// # use redis_lua::lua;
+/// let _ = lua!(x = 1 return x);
+/// ```
+///
+/// ```
+/// # use redis_lua::lua;
+/// let _ = lua!(local x = 1 return x);
+/// ```
+pub struct GlobalVariable;
+
+/// Possibly nil values in a returned table.
+///
+/// ```compile_fail
+/// #![deny(deprecated)]
+/// # use redis_lua::lua;
+/// let _ = lua!(return {1, redis.call("GET", "key")});
+/// ```
+///
+/// ```compile_fail
+/// #![deny(deprecated)]
+/// # use redis_lua::lua;
+/// let x: Option<i32> = None;
+/// let _ = lua!(return {@x, 1});
+/// ```
+///
+/// ```
+/// #![deny(deprecated)]
+/// # use redis_lua::lua;
+/// let x: Option<i32> = None;
+/// let _ = lua!(return {@x or false, redis.call("GET", "key") or false});
+/// ```
+pub struct NilInReturnedTable;
+
+/// The local variables of the prelude count against the limit of 200.
+///
+/// ```compile_fail
+/// # use redis_lua::lua;
+/// let _ = lua!(local a0, a1, a2, a3, a4, a5, a6, a7, a8, a9, a10, a11, a12, a13, a14, a15, a16, a17, a18, a19, a20, a21, a22, a23, a24, a25, a26, a27, a28, a29, a30, a31, a32, a33, a34, a35, a36, a37, a38, a39, a40, a41, a42, a43, a44, a45, a46, a47, a48, a49, a50, a51, a52, a53, a54, a55, a56, a57, a58, a59, a60, a61, a62, a63, a64, a65, a66, a67, a68, a69, a70, a71, a72, a73, a74, a75, a76, a77, a78, a79, a80, a81, a82, a83, a84, a85, a86, a87, a88, a89, a90, a91, a92, a93, a94, a95, a96, a97, a98, a99, a100, a101, a102, a103, a104, a105, a106, a107, a108, a109, a110, a111, a112, a113, a114, a115, a116, a117, a118, a119, a120, a121, a122, a123, a124, a125, a126, a127, a128, a129, a130, a131, a132, a133, a134, a135, a136, a137, a138, a139, a140, a141, a142, a143, a144, a145, a146, a147, a148, a149, a150, a151, a152, a153, a154, a155, a156, a157, a158, a159, a160, a161, a162, a163, a164, a165, a166, a167, a168, a169, a170, a171, a172, a173, a174, a175, a176, a177, a178, a179, a180, a181, a182, a183, a184, a185, a186, a187, a188, a189, a190, a191, a192, a193, a194, a195, a196, a197 = 1);
+/// ```
+///
+/// ```
+/// # use redis_lua::lua;
+/// let _ = lua!(local a0, a1, a2, a3, a4, a5, a6, a7, a8, a9, a10, a11, a12, a13, a14, a15, a16, a17, a18, a19, a20, a21, a22, a23, a24, a25, a26, a27, a28, a29, a30, a31, a32, a33, a34, a35, a36, a37, a38, a39, a40, a41, a42, a43, a44, a45, a46, a47, a48, a49, a50, a51, a52, a53, a54, a55, a56, a57, a58, a59, a60, a61, a62, a63, a64, a65, a66, a67, a68, a69, a70, a71, a72, a73, a74, a75, a76, a77, a78, a79, a80, a81, a82, a83, a84, a85, a86, a87, a88, a89, a90, a91, a92, a93, a94, a95, a96, a97, a98, a99, a100, a101, a102, a103, a104, a105, a106, a107, a108, a109, a110, a111, a112, a113, a114, a115, a116, a117, a118, a119, a120, a121, a122, a123, a124, a125, a126, a127, a128, a129, a130, a131, a132, a133, a134, a135, a136, a137, a138, a139, a140, a141, a142, a143, a144, a145, a146, a147, a148, a149, a150, a151, a152, a153, a154, a155, a156, a157, a158, a159, a160, a161, a162, a163, a164, a165, a166, a167, a168, a169, a170, a171, a172, a173, a174, a175, a176, a177, a178, a179, a180, a181, a182, a183, a184, a185, a186, a187, a188, a189, a190, a191, a192, a193, a194, a195, a196 = 1);
+/// ```
+pub struct TooManyLocals;
+
+/// Captured variables in a function library.
+///
+/// ```compile_fail
+/// # use redis_lua::lua_library;
+/// lua_library! {
+///     name = "lint";
+///
+///     fn get(key) {
+///         return redis.call("GET", @key)
+///     }
+/// }
+/// # fn main() {}
+/// ```
+///
+/// ```
+/// # use redis_lua::lua_library;
+/// lua_library! {
+///     name = "lint";
+///
+///     fn get(key) {
+///         return redis.call("GET", $key)
+///     }
+/// }
+/// # fn main() {}
+/// ```
+pub struct LibraryCapture;
+
+/// Variants of `ScriptError` tagged by an error code of Redis.
+///
+/// ```compile_fail
+/// # use redis_lua::ScriptError;
+/// #[derive(ScriptError)]
+/// enum TransferError {
+///     Busy,
+/// }
+/// ```
+///
+/// ```
+/// # use redis_lua::ScriptError;
+/// #[derive(ScriptError)]
+/// enum TransferError {
+///     AccountBusy,
+/// }
+/// ```
+pub struct ReservedTag;
@@ -0,0 +1,284 @@
+use crate::{
+    error::Error,
+    gen::Generated,
+    script::{Info, Script},
+    types::ScriptArg,
+};
+use futures::{channel::oneshot, future};
+use std::{
+    collections::BTreeMap,
+    sync::{Condvar, Mutex, MutexGuard, Once, OnceLock, PoisonError},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Options to invoke long-running scripts.
+#[derive(Clone, Debug)]
+pub struct Options {
+    deadline: Option<(Duration, redis::ConnectionInfo)>,
+    busy_retries: usize,
+    backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            deadline: None,
+            busy_retries: 0,
+            backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+impl Options {
+    /// Create the default options, which neither kill the script nor retry on `BUSY`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send `SCRIPT KILL` on a separate connection to `server` if the script is running after `deadline`.
+    ///
+    /// Only the scripts calling read-only commands are killed, as Redis refuses to kill a script
+    /// once it has written. Note that `SCRIPT KILL` kills whichever script is running at the moment.
+    pub fn deadline(mut self, deadline: Duration, server: redis::ConnectionInfo) -> Self {
+        self.deadline = Some((deadline, server));
+        self
+    }
+
+    /// Retry up to `retries` times while the server is busy running another script (`BUSY`).
+    pub fn busy_retries(mut self, retries: usize) -> Self {
+        self.busy_retries = retries;
+        self
+    }
+
+    /// Wait `initial` before the first retry on `BUSY`, doubling it on each retry up to `max`.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    /// The time to wait before the `retry`-th retry, starting from 0.
+    fn backoff_at(&self, retry: usize) -> Duration {
+        let factor = 1u32.checked_shl(retry as u32).unwrap_or(u32::MAX);
+        self.backoff
+            .checked_mul(factor)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff)
+    }
+
+    /// Schedule `SCRIPT KILL` at the deadline if the script can be killed.
+    fn watchdog(&self, script: &Generated) -> Option<Scheduled> {
+        let (deadline, server) = match &self.deadline {
+            Some((deadline, server)) if script.killable() => (deadline, server.clone()),
+            _ => return None,
+        };
+        let at = Instant::now().checked_add(*deadline)?;
+
+        Some(Timer::get().schedule(
+            at,
+            Box::new(move || {
+                // Connecting may take a while; don't hold up the other tasks of the timer.
+                let _ = thread::Builder::new().spawn(move || kill(server));
+            }),
+        ))
+    }
+
+    /// Invoke the script, killing it after the deadline and retrying while the server is busy.
+    pub(crate) fn invoke<T>(
+        &self,
+        script: &Generated,
+        con: &mut dyn redis::ConnectionLike,
+        keys: &[Vec<u8>],
+        args: &[ScriptArg],
+    ) -> Result<T, Error>
+    where
+        T: redis::FromRedisValue,
+    {
+        let mut retry = 0;
+        loop {
+            let watchdog = self.watchdog(script);
+            let res = script.invoke(con, keys, args);
+            drop(watchdog);
+
+            match res {
+                Err(Error::Busy(_)) if retry < self.busy_retries => {
+                    thread::sleep(self.backoff_at(retry));
+                    retry += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+
+    /// Invoke the script asynchronously with the options.
+    pub(crate) async fn invoke_async<C, T>(
+        &self,
+        script: &Generated<'_>,
+        con: &mut C,
+        keys: &[Vec<u8>],
+        args: &[ScriptArg],
+    ) -> Result<T, Error>
+    where
+        C: redis::aio::ConnectionLike + Send,
+        T: redis::FromRedisValue + Send,
+    {
+        let mut retry = 0;
+        loop {
+            let watchdog = self.watchdog(script);
+            let res = script.invoke_async(con, keys, args).await;
+            drop(watchdog);
+
+            match res {
+                Err(Error::Busy(_)) if retry < self.busy_retries => {
+                    sleep(self.backoff_at(retry)).await;
+                    retry += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+}
+
+/// Send `SCRIPT KILL` on a new connection.
+fn kill(server: redis::ConnectionInfo) {
+    // Failures are ignored; e.g. `NOTBUSY` if the script has just finished.
+    let _ = redis::Client::open(server)
+        .and_then(|cli| cli.get_connection())
+        .and_then(|mut con| redis::cmd("SCRIPT").arg("KILL").query::<()>(&mut con));
+}
+
+type Task = Box<dyn FnOnce() + Send>;
+
+#[derive(Default)]
+struct Tasks {
+    next_id: u64,
+    /// The tasks ordered by the time to run them.
+    queue: BTreeMap<(Instant, u64), Task>,
+}
+
+/// A single thread running the tasks at their time, shared by all the invocations,
+/// so that neither a deadline nor a backoff takes a thread of its own.
+#[derive(Default)]
+struct Timer {
+    tasks: Mutex<Tasks>,
+    changed: Condvar,
+}
+
+impl Timer {
+    fn get() -> &'static Self {
+        static TIMER: OnceLock<Timer> = OnceLock::new();
+        static START: Once = Once::new();
+
+        let timer = TIMER.get_or_init(Timer::default);
+        START.call_once(|| {
+            let _ = thread::Builder::new()
+                .name("redis-lua-timer".into())
+                .spawn(move || timer.run());
+        });
+        timer
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Tasks> {
+        // The tasks run without the lock, so it's never poisoned by them.
+        self.tasks.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Run `task` at `at` unless the returned handle is dropped before that.
+    fn schedule(&'static self, at: Instant, task: Task) -> Scheduled {
+        let mut tasks = self.lock();
+        tasks.next_id += 1;
+        let key = (at, tasks.next_id);
+        tasks.queue.insert(key, task);
+        self.changed.notify_one();
+
+        Scheduled { timer: self, key }
+    }
+
+    fn run(&self) {
+        let mut tasks = self.lock();
+        loop {
+            let now = Instant::now();
+            tasks = match tasks.queue.keys().next().copied() {
+                Some(key) if key.0 <= now => {
+                    let task = tasks.queue.remove(&key);
+                    drop(tasks);
+                    if let Some(task) = task {
+                        task();
+                    }
+                    self.lock()
+                }
+                Some((at, _)) => {
+                    self.changed
+                        .wait_timeout(tasks, at - now)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0
+                }
+                None => self
+                    .changed
+                    .wait(tasks)
+                    .unwrap_or_else(PoisonError::into_inner),
+            };
+        }
+    }
+}
+
+/// Task scheduled on the timer, which is cancelled when dropped.
+struct Scheduled {
+    timer: &'static Timer,
+    key: (Instant, u64),
+}
+
+impl Drop for Scheduled {
+    fn drop(&mut self) {
+        self.timer.lock().queue.remove(&self.key);
+    }
+}
+
+/// Wait on the shared timer without depending on a specific async runtime.
+async fn sleep(duration: Duration) {
+    let at = match Instant::now().checked_add(duration) {
+        Some(at) => at,
+        None => return future::pending().await,
+    };
+
+    let (tx, rx) = oneshot::channel();
+    let _scheduled = Timer::get().schedule(
+        at,
+        Box::new(move || {
+            let _ = tx.send(());
+        }),
+    );
+    let _ = rx.await;
+}
+
+/// Script invoked with the options.
+///
+/// The options apply however the script is wrapped afterwards, e.g. by `route`,
+/// but not in [`ScriptPipeline`](crate::ScriptPipeline), which can't retry a single script.
+pub struct WithOptions<S> {
+    script: S,
+    options: Options,
+}
+
+impl<S> WithOptions<S> {
+    pub(crate) fn new(script: S, options: Options) -> Self {
+        Self { script, options }
+    }
+}
+
+impl<S: Script> Script for WithOptions<S> {
+    fn info(&self, info: &mut Vec<Info>, args: &mut Vec<ScriptArg>) {
+        self.script.info(info, args);
+    }
+
+    fn route_keys(&self, keys: &mut Vec<Vec<u8>>) {
+        self.script.route_keys(keys);
+    }
+
+    fn invoke_options(&self) -> Option<&Options> {
+        Some(&self.options)
+    }
+}
@@ -0,0 +1,148 @@
+use crate::{
+    cluster,
+    error::Error,
+    gen::Generated,
+    script::{Info, Script},
+};
+use std::collections::HashSet;
+
+/// Pipeline of scripts and commands sent in a single round trip.
+///
+/// The first occurrence of each script is queued as `EVAL`, which caches the script
+/// in the server, and the later ones as `EVALSHA`. So the scripts never fail with `NOSCRIPT`
+/// even if the script cache is flushed before the pipeline is sent, and each command runs once.
+/// In a pipeline which isn't atomic, a later `EVALSHA` still fails if another client runs
+/// `SCRIPT FLUSH` while the pipeline is running.
+///
+/// The keys given to the scripts by `route` are passed as `KEYS`, and all of them must hash
+/// to the same slot in Redis Cluster, as the pipeline is sent to a single node.
+/// The pipeline isn't routed by the keys, so it must be sent through a connection to the node
+/// owning the slot; `redis::cluster::ClusterConnection` refuses pipelines.
+/// The scripts with the keys are always queued as `EVAL`, as `SCRIPT LOAD` can't be routed.
+#[derive(Clone, Default)]
+pub struct ScriptPipeline {
+    /// The commands and whether their results are ignored.
+    commands: Vec<(redis::Cmd, bool)>,
+    /// The script information of each script in the pipeline.
+    scripts: Vec<Vec<Info>>,
+    /// The hashes of the scripts queued as `EVAL`.
+    sent: HashSet<String>,
+    /// The keys of all the scripts.
+    keys: Vec<Vec<u8>>,
+    atomic: bool,
+}
+
+impl ScriptPipeline {
+    /// Create an empty pipeline.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run the pipeline in a transaction by `MULTI`/`EXEC`.
+    pub fn atomic(&mut self) -> &mut Self {
+        self.atomic = true;
+        self
+    }
+
+    /// Add a script.
+    pub fn script<S: Script + ?Sized>(&mut self, script: &S) -> &mut Self {
+        let mut info = vec![];
+        let mut args = vec![];
+        let mut keys = vec![];
+        script.info(&mut info, &mut args);
+        script.route_keys(&mut keys);
+
+        let gen = Generated::get(&info);
+        let cmd = if keys.is_empty() && self.sent.contains(gen.hash()) {
+            gen.evalsha(&keys, &args)
+        } else {
+            self.sent.insert(gen.hash().to_string());
+            gen.eval_code(&keys, &args)
+        };
+        self.commands.push((cmd, false));
+        self.scripts.push(info);
+        self.keys.extend(keys);
+        self
+    }
+
+    /// Add a command.
+    pub fn cmd(&mut self, cmd: redis::Cmd) -> &mut Self {
+        self.commands.push((cmd, false));
+        self
+    }
+
+    /// Ignore the result of the last script or command.
+    pub fn ignore(&mut self) -> &mut Self {
+        if let Some((_, ignore)) = self.commands.last_mut() {
+            *ignore = true;
+        }
+        self
+    }
+
+    fn build(&self) -> redis::Pipeline {
+        let mut pipe = redis::pipe();
+        if self.atomic {
+            pipe.atomic();
+        }
+
+        for (cmd, ignore) in &self.commands {
+            pipe.add_command(cmd.clone());
+            if *ignore {
+                pipe.ignore();
+            }
+        }
+        pipe
+    }
+
+    /// Check the pipeline before sending anything, which fails if the keys span multiple slots.
+    pub(crate) fn check(&self) -> Result<(), Error> {
+        cluster::check_slot(&self.keys).map(|_| ())
+    }
+
+    /// Send the pipeline without checking it.
+    pub(crate) fn send<T>(&self, con: &mut dyn redis::ConnectionLike) -> Result<T, Error>
+    where
+        T: redis::FromRedisValue,
+    {
+        self.build().query(con).map_err(|e| self.error(e))
+    }
+
+    /// Send the pipeline asynchronously without checking it.
+    pub(crate) async fn send_async<C, T>(&self, con: &mut C) -> Result<T, Error>
+    where
+        C: redis::aio::ConnectionLike + Send,
+        T: redis::FromRedisValue + Send,
+    {
+        self.build()
+            .query_async(con)
+            .await
+            .map_err(|e| self.error(e))
+    }
+
+    /// The lines of the script are known only if the pipeline has a single script.
+    fn error(&self, err: redis::RedisError) -> Error {
+        match self.scripts.as_slice() {
+            [info] => Generated::get(info).error(err),
+            _ => Error::new(err, None),
+        }
+    }
+
+    /// Send the pipeline.
+    pub fn query<T>(&self, con: &mut dyn redis::ConnectionLike) -> Result<T, Error>
+    where
+        T: redis::FromRedisValue,
+    {
+        self.check()?;
+        self.send(con)
+    }
+
+    /// Send the pipeline asynchronously.
+    pub async fn query_async<C, T>(&self, con: &mut C) -> Result<T, Error>
+    where
+        C: redis::aio::ConnectionLike + Send,
+        T: redis::FromRedisValue + Send,
+    {
+        self.check()?;
+        self.send_async(con).await
+    }
+}
@@ -0,0 +1,148 @@
+use crate::{error::Error, lines::Location};
+use std::collections::HashSet;
+
+/// Script registered by `lua!` at compile time.
+///
+/// Only the script itself is registered; scripts joined at runtime are not.
+#[derive(Debug)]
+pub struct Registered {
+    name: &'static str,
+    location: Location,
+    code: &'static str,
+    hash: &'static str,
+    killable: bool,
+}
+
+impl Registered {
+    #[doc(hidden)]
+    pub const fn new(
+        name: &'static str,
+        location: Location,
+        code: &'static str,
+        hash: &'static str,
+        killable: bool,
+    ) -> Self {
+        Self {
+            name,
+            location,
+            code,
+            hash,
+            killable,
+        }
+    }
+
+    /// The name of the script, which is the module path followed by the location,
+    /// e.g. `app::jobs (src/jobs.rs:10:5)`.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The location of the script in the Rust source code.
+    pub fn location(&self) -> Location {
+        self.location
+    }
+
+    /// The complete script sent to Redis.
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+
+    /// The SHA1 hash of the script.
+    pub fn hash(&self) -> &'static str {
+        self.hash
+    }
+
+    /// Returns `true` if the script can be killed by `SCRIPT KILL`,
+    /// i.e. it calls only read-only commands.
+    pub fn killable(&self) -> bool {
+        self.killable
+    }
+}
+
+inventory::collect!(Registered);
+
+/// All the scripts in the binary.
+pub fn scripts() -> impl Iterator<Item = &'static Registered> {
+    inventory::iter::<Registered>.into_iter()
+}
+
+/// The scripts without duplicates of the same text.
+fn unique() -> Vec<&'static Registered> {
+    let mut seen = HashSet::new();
+    scripts().filter(|s| seen.insert(s.hash)).collect()
+}
+
+fn load_all(scripts: &[&Registered]) -> redis::Pipeline {
+    let mut pipe = redis::pipe();
+    for s in scripts {
+        pipe.cmd("SCRIPT").arg("LOAD").arg(s.code).ignore();
+    }
+    pipe
+}
+
+fn exists(scripts: &[&Registered]) -> redis::Cmd {
+    let mut cmd = redis::cmd("SCRIPT");
+    cmd.arg("EXISTS");
+    for s in scripts {
+        cmd.arg(s.hash);
+    }
+    cmd
+}
+
+fn not_cached(scripts: Vec<&'static Registered>, exists: Vec<bool>) -> Vec<&'static Registered> {
+    scripts
+        .into_iter()
+        .zip(exists)
+        .filter(|(_, exists)| !exists)
+        .map(|(s, _)| s)
+        .collect()
+}
+
+/// Load all the scripts in the binary by `SCRIPT LOAD`, e.g. at startup.
+pub fn preload_scripts(con: &mut dyn redis::ConnectionLike) -> Result<(), Error> {
+    let scripts = unique();
+    if scripts.is_empty() {
+        return Ok(());
+    }
+    load_all(&scripts).query(con).map_err(Error::from)
+}
+
+/// Load all the scripts in the binary by `SCRIPT LOAD` asynchronously.
+pub async fn preload_scripts_async<C>(con: &mut C) -> Result<(), Error>
+where
+    C: redis::aio::ConnectionLike + Send,
+{
+    let scripts = unique();
+    if scripts.is_empty() {
+        return Ok(());
+    }
+    load_all(&scripts)
+        .query_async(con)
+        .await
+        .map_err(Error::from)
+}
+
+/// Check the scripts in the binary by `SCRIPT EXISTS`, returning the ones not cached in the server.
+pub fn check_scripts(
+    con: &mut dyn redis::ConnectionLike,
+) -> Result<Vec<&'static Registered>, Error> {
+    let scripts = unique();
+    if scripts.is_empty() {
+        return Ok(vec![]);
+    }
+    let exists = exists(&scripts).query(con)?;
+    Ok(not_cached(scripts, exists))
+}
+
+/// Check the scripts in the binary by `SCRIPT EXISTS` asynchronously.
+pub async fn check_scripts_async<C>(con: &mut C) -> Result<Vec<&'static Registered>, Error>
+where
+    C: redis::aio::ConnectionLike + Send,
+{
+    let scripts = unique();
+    if scripts.is_empty() {
+        return Ok(vec![]);
+    }
+    let exists = exists(&scripts).query_async(con).await?;
+    Ok(not_cached(scripts, exists))
+}
@@ -1,21 +1,114 @@
-use crate::types::ScriptArg;
-use futures::prelude::*;
+use crate::{
+    cluster::{self, Routed},
+    error::Error,
+    failure::Raising,
+    fanout::{self, NodeResults, Primaries},
+    gen::Generated,
+    lines::Location,
+    options::{Options, WithOptions},
+    types::ScriptArg,
+};
+use futures::{future::BoxFuture, prelude::*};
+
+/// The maximum number of active local variables in a Lua 5.1 function.
+const MAX_LOCALS: usize = 200;
+
+/// The maximum number of upvalues in a Lua 5.1 function.
+const MAX_UPVALUES: usize = 60;
 
 /// Script information which is generated by proc-macro.
 #[derive(Clone, Debug)]
 pub struct Info {
-    /// The entire script including arguments initialization.
-    script: &'static str,
     /// The script excluding arguments initialization.
     body: &'static str,
+    /// Same as `body` but the arguments are referred through a table.
+    table: &'static str,
+    /// The name of the table which `table` refers to.
+    arg_table: &'static str,
     /// The list of arguments.
     args: &'static [&'static str],
+    /// The number of local variables in `body`.
+    locals: usize,
+    /// The number of upvalues in `body` when each argument is a local variable.
+    upvalues: usize,
+    /// The location of the first line of `body` in the Rust source code.
+    location: Location,
+    /// The complete script to be invoked when it's not joined.
+    code: &'static str,
+    /// The SHA1 hash of `code`.
+    hash: &'static str,
+    /// Whether the script calls only read-only commands, so that it can be killed by `SCRIPT KILL`.
+    killable: bool,
 }
 
 impl Info {
     /// Create the new script information.
-    pub fn new(script: &'static str, body: &'static str, args: &'static [&'static str]) -> Self {
-        Self { script, body, args }
+    pub fn new(
+        body: &'static str,
+        table: &'static str,
+        arg_table: &'static str,
+        args: &'static [&'static str],
+        locals: usize,
+        upvalues: usize,
+        location: Location,
+        code: &'static str,
+        hash: &'static str,
+        killable: bool,
+    ) -> Self {
+        Self {
+            body,
+            table,
+            arg_table,
+            args,
+            locals,
+            upvalues,
+            location,
+            code,
+            hash,
+            killable,
+        }
+    }
+
+    /// The location of the script in the Rust source code.
+    pub fn location(&self) -> Location {
+        self.location
+    }
+
+    /// The complete script to be invoked when it's not joined.
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+
+    /// The SHA1 hash of the script when it's not joined, which is computed at compile time.
+    pub fn hash(&self) -> &'static str {
+        self.hash
+    }
+
+    /// Returns `true` if the script can be killed by `SCRIPT KILL`,
+    /// i.e. it calls only read-only commands.
+    pub fn killable(&self) -> bool {
+        self.killable
+    }
+
+    pub(crate) fn body(&self) -> &'static str {
+        self.body
+    }
+
+    pub(crate) fn table(&self) -> &'static str {
+        self.table
+    }
+
+    pub(crate) fn arg_table(&self) -> &'static str {
+        self.arg_table
+    }
+
+    pub(crate) fn args(&self) -> &'static [&'static str] {
+        self.args
+    }
+
+    /// Returns `true` if the arguments don't fit in local variables.
+    pub(crate) fn table_layout(&self) -> bool {
+        self.locals + self.args.len() > MAX_LOCALS || self.upvalues > MAX_UPVALUES
     }
 }
 
@@ -27,6 +120,14 @@ pub trait Script {
     /// Retrieve all the script information.
     fn info(&self, _: &mut Vec<Info>, _: &mut Vec<ScriptArg>);
 
+    /// Retrieve the keys given by `route`, which are passed as `KEYS`.
+    fn route_keys(&self, _: &mut Vec<Vec<u8>>) {}
+
+    /// The options given by `with_options`.
+    fn invoke_options(&self) -> Option<&Options> {
+        None
+    }
+
     /// Join another script making self as inner.
     fn join<T: Script>(self, other: T) -> ScriptJoin<Self, T>
     where
@@ -35,42 +136,71 @@ pub trait Script {
         ScriptJoin(self, other)
     }
 
+    /// Decode the user-defined error `E` raised by `script_error` on failure.
+    fn raising<E>(self) -> Raising<Self, E>
+    where
+        Self: Sized,
+    {
+        Raising::new(self)
+    }
+
+    /// Pass the keys to route the script to the node owning their slot in Redis Cluster.
+    ///
+    /// The keys must hash to the same slot, which is checked before sending the script.
+    /// They are given as `KEYS`, so that Redis routes the script and checks the ACL key patterns;
+    /// pass them as `$` variables as well to use them.
+    fn route<K>(self, keys: &[K]) -> Routed<Self>
+    where
+        K: redis::ToRedisArgs,
+        Self: Sized,
+    {
+        Routed::new(self, keys.iter().flat_map(|k| k.to_redis_args()).collect())
+    }
+
+    /// Invoke the script with the options for long-running scripts, e.g. the deadline.
+    ///
+    /// The outermost options apply if they are given more than once.
+    fn with_options(self, options: Options) -> WithOptions<Self>
+    where
+        Self: Sized,
+    {
+        WithOptions::new(self, options)
+    }
+
     /// Invoke the script.
-    fn invoke<T>(self, con: &mut dyn redis::ConnectionLike) -> redis::RedisResult<T>
+    fn invoke<T>(self, con: &mut dyn redis::ConnectionLike) -> Result<T, Error>
     where
         T: redis::FromRedisValue,
         Self: Sized,
     {
-        let mut info = vec![];
-        let mut args = vec![];
-        self.info(&mut info, &mut args);
-        let script = gen_script(&info, &args);
-        let mut invoke = script.prepare_invoke();
-        for wr in args {
-            invoke.arg(wr);
-        }
-        invoke.invoke(con)
+        Invocation::new(&self)?.invoke(con)
+    }
+
+    /// Invoke the script on each primary node of Redis Cluster concurrently.
+    ///
+    /// The connections of `primaries` are reused across invocations. The options given by
+    /// `with_options` apply on each node; a routed script is an error as its keys belong to
+    /// a single node.
+    fn invoke_on_all_primaries<C, T>(
+        self,
+        primaries: &Primaries<C>,
+    ) -> Result<NodeResults<T>, Error>
+    where
+        C: redis::ConnectionLike + Send,
+        T: redis::FromRedisValue + Send,
+        Self: Sized,
+    {
+        fanout::invoke_on_all_primaries(&self, primaries)
     }
 
     /// Invoke the script asynchronously.
-    fn invoke_async<'a, C, T>(self, con: &'a mut C) -> redis::RedisFuture<'a, T>
+    fn invoke_async<'a, C, T>(self, con: &'a mut C) -> BoxFuture<'a, Result<T, Error>>
     where
         C: redis::aio::ConnectionLike + Send,
         T: redis::FromRedisValue + Send,
         Self: Sized + Send + 'a,
     {
-        async move {
-            let mut info = vec![];
-            let mut args = vec![];
-            self.info(&mut info, &mut args);
-            let script = gen_script(&info, &args);
-            let mut invoke = script.prepare_invoke();
-            for wr in args {
-                invoke.arg(wr);
-            }
-            invoke.invoke_async(con).await
-        }
-        .boxed()
+        async move { Invocation::new(&self)?.invoke_async(con).await }.boxed()
     }
 }
 
@@ -78,6 +208,14 @@ impl<S: Script + ?Sized> Script for Box<S> {
     fn info(&self, infos: &mut Vec<Info>, args: &mut Vec<ScriptArg>) {
         (**self).info(infos, args);
     }
+
+    fn route_keys(&self, keys: &mut Vec<Vec<u8>>) {
+        (**self).route_keys(keys);
+    }
+
+    fn invoke_options(&self) -> Option<&Options> {
+        (**self).invoke_options()
+    }
 }
 
 impl Script for () {
@@ -96,41 +234,70 @@ where
         self.0.info(info, args);
         self.1.info(info, args);
     }
-}
 
-/// Take another script as the inner of the script.
-pub trait TakeScript<I> {
-    type Item;
+    fn route_keys(&self, keys: &mut Vec<Vec<u8>>) {
+        self.0.route_keys(keys);
+        self.1.route_keys(keys);
+    }
 
-    /// Take the inner script.
-    fn take(self, inner: I) -> Self::Item;
+    fn invoke_options(&self) -> Option<&Options> {
+        self.0.invoke_options().or_else(|| self.1.invoke_options())
+    }
 }
 
-/// Generate a script from a list of script information.
-pub fn gen_script(info: &[Info], args: &[ScriptArg]) -> redis::Script {
-    assert!(info.len() > 0, "No script information");
-
-    // Generate the joined script.
-    let mut arg_index = 0;
-    let mut script = String::new();
-    let last = info.len() - 1;
-    for (index, info) in info.iter().enumerate() {
-        let prefix = if index == last { "return " } else { "" };
-        let mut init = String::new();
+/// Everything collected from a script to invoke it, including what the wrappers
+/// such as [`Routed`](Routed) and [`WithOptions`](WithOptions) add, so that they compose
+/// in any order.
+pub(crate) struct Invocation {
+    info: Vec<Info>,
+    args: Vec<ScriptArg>,
+    keys: Vec<Vec<u8>>,
+    options: Options,
+}
 
-        for arg in info.args {
-            let pack = args[arg_index].pack();
+impl Invocation {
+    /// Collect the script; an error if the keys span multiple slots.
+    pub(crate) fn new<S: Script + ?Sized>(script: &S) -> Result<Self, Error> {
+        let mut info = vec![];
+        let mut args = vec![];
+        let mut keys = vec![];
+        script.info(&mut info, &mut args);
+        script.route_keys(&mut keys);
+        cluster::check_slot(&keys)?;
+        let options = script.invoke_options().cloned().unwrap_or_default();
 
-            arg_index += 1;
+        Ok(Self {
+            info,
+            args,
+            keys,
+            options,
+        })
+    }
 
-            if pack {
-                init += &format!("local {} = cmsgpack.unpack(ARGV[{}]) ", arg, arg_index);
-            } else {
-                init += &format!("local {} = ARGV[{}] ", arg, arg_index);
-            }
-        }
+    pub(crate) fn invoke<T>(&self, con: &mut dyn redis::ConnectionLike) -> Result<T, Error>
+    where
+        T: redis::FromRedisValue,
+    {
+        let script = Generated::get(&self.info);
+        self.options.invoke(&script, con, &self.keys, &self.args)
+    }
 
-        script += &format!("{}(function() {} {} end)();\n", prefix, init, info.body);
+    pub(crate) async fn invoke_async<C, T>(&self, con: &mut C) -> Result<T, Error>
+    where
+        C: redis::aio::ConnectionLike + Send,
+        T: redis::FromRedisValue + Send,
+    {
+        let script = Generated::get(&self.info);
+        self.options
+            .invoke_async(&script, con, &self.keys, &self.args)
+            .await
     }
-    redis::Script::new(&script)
+}
+
+/// Take another script as the inner of the script.
+pub trait TakeScript<I> {
+    type Item;
+
+    /// Take the inner script.
+    fn take(self, inner: I) -> Self::Item;
 }
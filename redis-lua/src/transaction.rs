@@ -0,0 +1,76 @@
+use crate::{error::Error, pipeline::ScriptPipeline};
+use futures::future::BoxFuture;
+
+/// Run scripts and commands in a transaction by `MULTI`/`EXEC`, watching `keys` by `WATCH`.
+///
+/// `func` reads the keys through the connection and queues the scripts and commands
+/// to the pipeline, which is already atomic. If any of the keys is modified before `EXEC`,
+/// the transaction is aborted and `func` is called again with a new pipeline.
+/// The result is the results of the queued scripts and commands which are not ignored.
+///
+/// The scripts are sent by `EVAL` in the transaction, so that they don't fail with `NOSCRIPT`
+/// in the middle of it. See [`ScriptPipeline`](ScriptPipeline).
+///
+/// The transaction isn't routed in Redis Cluster, so `con` must be connected to the node
+/// owning the slot of the keys.
+pub fn transaction<C, K, T, F>(con: &mut C, keys: &[K], mut func: F) -> Result<T, Error>
+where
+    C: redis::ConnectionLike,
+    K: redis::ToRedisArgs,
+    T: redis::FromRedisValue,
+    F: FnMut(&mut C, &mut ScriptPipeline) -> Result<(), Error>,
+{
+    loop {
+        redis::cmd("WATCH").arg(keys).query::<()>(con)?;
+
+        let mut pipe = ScriptPipeline::new();
+        pipe.atomic();
+
+        let res = func(con, &mut pipe).and_then(|_| pipe.check());
+        if let Err(e) = res {
+            redis::cmd("UNWATCH").query::<()>(con)?;
+            return Err(e);
+        }
+
+        // `EXEC` returns nil if the transaction is aborted.
+        if let Some(res) = pipe.send::<Option<T>>(con)? {
+            return Ok(res);
+        }
+    }
+}
+
+/// Run scripts and commands in a transaction asynchronously. See [`transaction`](transaction).
+///
+/// The connection must not be shared with other tasks, e.g. `MultiplexedConnection`,
+/// as `WATCH` applies to the connection.
+pub async fn transaction_async<C, K, T, F>(con: &mut C, keys: &[K], mut func: F) -> Result<T, Error>
+where
+    C: redis::aio::ConnectionLike + Send,
+    K: redis::ToRedisArgs,
+    T: redis::FromRedisValue + Send,
+    F: for<'a> FnMut(&'a mut C, &'a mut ScriptPipeline) -> BoxFuture<'a, Result<(), Error>>,
+{
+    loop {
+        redis::cmd("WATCH")
+            .arg(keys)
+            .query_async::<_, ()>(con)
+            .await?;
+
+        let mut pipe = ScriptPipeline::new();
+        pipe.atomic();
+
+        let res = match func(con, &mut pipe).await {
+            Ok(()) => pipe.check(),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = res {
+            redis::cmd("UNWATCH").query_async::<_, ()>(con).await?;
+            return Err(e);
+        }
+
+        // `EXEC` returns nil if the transaction is aborted.
+        if let Some(res) = pipe.send_async::<_, Option<T>>(con).await? {
+            return Ok(res);
+        }
+    }
+}
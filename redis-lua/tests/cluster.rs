@@ -0,0 +1,211 @@
+use redis_lua::{lua, slot, Error, Options, Script, ScriptPipeline};
+
+#[test]
+fn hash_slot() {
+    assert_eq!(slot(b"foo"), 12182);
+    assert_eq!(slot(b"bar"), 5061);
+
+    // Only the hashtag is hashed.
+    assert_eq!(slot(b"{user1000}.following"), slot(b"user1000"));
+    assert_eq!(slot(b"foo{bar}{zap}"), slot(b"bar"));
+    assert_eq!(slot(b"foo{{bar}}zap"), slot(b"{bar"));
+
+    // Empty or unclosed hashtags are ignored.
+    assert_eq!(slot(b"foo{}{bar}"), 8363);
+    assert_ne!(slot(b"foo{bar"), slot(b"bar"));
+}
+
+#[test]
+fn cross_slot() {
+    let mut cli = redis::Client::open("redis://127.0.0.1").unwrap();
+
+    let script = lua!(return redis.call("GET", $a) or redis.call("GET", $b));
+    let err = script
+        .a("foo")
+        .b("bar")
+        .route(&["foo", "bar"])
+        .invoke::<()>(&mut cli)
+        .unwrap_err();
+    match err {
+        Error::CrossSlot(e) => {
+            assert!(
+                e.message().contains("`foo` is in slot 12182"),
+                "{}",
+                e.message()
+            )
+        }
+        e => panic!("unexpected error: {:?}", e),
+    }
+}
+
+#[tokio::test]
+async fn route() {
+    let cli = redis::Client::open("redis://127.0.0.1").unwrap();
+    let mut con = cli.get_multiplexed_tokio_connection().await.unwrap();
+
+    let (k1, k2) = ("{cluster_route}.a", "{cluster_route}.b");
+    let script = lua! {
+        redis.call("SET", $k1, 1)
+        redis.call("SET", $k2, 2)
+        return redis.call("GET", $k1) + redis.call("GET", $k2)
+    };
+    let sum: usize = script
+        .k1(k1)
+        .k2(k2)
+        .route(&[k1, k2])
+        .invoke_async(&mut con)
+        .await
+        .unwrap();
+    assert_eq!(sum, 3);
+}
+
+fn assert_cross_slot<T: std::fmt::Debug>(res: Result<T, Error>) {
+    match res {
+        Err(Error::CrossSlot(_)) => {}
+        res => panic!("unexpected result: {:?}", res),
+    }
+}
+
+#[test]
+fn cross_slot_through_wrappers() {
+    let mut cli = redis::Client::open("redis://127.0.0.1").unwrap();
+    let script = || lua!(return 1);
+
+    // The keys are checked however the routed script is invoked.
+    assert_cross_slot(
+        script()
+            .route(&["foo", "bar"])
+            .with_options(Options::new())
+            .invoke::<usize>(&mut cli),
+    );
+    assert_cross_slot(
+        script()
+            .with_options(Options::new())
+            .route(&["foo", "bar"])
+            .invoke::<usize>(&mut cli),
+    );
+    assert_cross_slot(
+        script()
+            .route(&["foo"])
+            .join(script().route(&["bar"]))
+            .invoke::<usize>(&mut cli),
+    );
+    assert_cross_slot(
+        ScriptPipeline::new()
+            .script(&script().route(&["foo"]))
+            .script(&script().route(&["bar"]))
+            .query::<(usize, usize)>(&mut cli),
+    );
+}
+
+#[test]
+fn keys_through_wrappers() {
+    let cli = redis::Client::open("redis://127.0.0.1").unwrap();
+    let mut admin = cli.get_connection().unwrap();
+
+    // The user can access only the keys matching the pattern, which Redis checks on `KEYS`.
+    let user = "redis_lua_keys_through_wrappers";
+    redis::cmd("ACL")
+        .arg(
+            &[
+                "SETUSER",
+                user,
+                "reset",
+                "on",
+                "nopass",
+                "~allowed:*",
+                "+@all",
+            ][..],
+        )
+        .query::<()>(&mut admin)
+        .unwrap();
+    let mut con = cli.get_connection().unwrap();
+    redis::cmd("AUTH")
+        .arg(user)
+        .arg("-")
+        .query::<()>(&mut con)
+        .unwrap();
+
+    let script = || lua!(return 1);
+    for (key, allowed) in &[("allowed:a", true), ("denied:a", false)] {
+        let keys = [*key];
+        let results = vec![
+            script().route(&keys).invoke::<usize>(&mut con),
+            script()
+                .route(&keys)
+                .with_options(Options::new())
+                .invoke::<usize>(&mut con),
+            script()
+                .with_options(Options::new())
+                .route(&keys)
+                .invoke::<usize>(&mut con),
+            ScriptPipeline::new()
+                .script(&script().route(&keys))
+                .query::<(usize,)>(&mut con)
+                .map(|(n,)| n),
+        ];
+        for res in results {
+            assert_eq!(res.is_ok(), *allowed, "{}: {:?}", key, res);
+        }
+    }
+
+    redis::cmd("ACL")
+        .arg("DELUSER")
+        .arg(user)
+        .query::<()>(&mut admin)
+        .unwrap();
+}
+
+/// Connect to Redis Cluster at the nodes in `REDIS_CLUSTER_NODES`, e.g. `redis://127.0.0.1:7000`.
+fn cluster() -> redis::cluster::ClusterConnection {
+    let nodes =
+        std::env::var("REDIS_CLUSTER_NODES").unwrap_or_else(|_| "redis://127.0.0.1:7000".into());
+    redis::cluster::ClusterClient::open(nodes.split(',').collect())
+        .unwrap()
+        .get_connection()
+        .unwrap()
+}
+
+#[test]
+#[ignore] // Requires Redis Cluster.
+fn cluster_route() {
+    let mut con = cluster();
+
+    // The script fails unless it runs on the node owning the keys.
+    let script = || {
+        lua! {
+            redis.call("SET", $k1, 1)
+            redis.call("SET", $k2, 2)
+            return redis.call("GET", $k1) + redis.call("GET", $k2)
+        }
+    };
+    for tag in &["a", "b", "c", "d"] {
+        let (k1, k2) = (format!("{{{}}}.x", tag), format!("{{{}}}.y", tag));
+        let sum: usize = script()
+            .k1(&k1)
+            .k2(&k2)
+            .route(&[&k1, &k2])
+            .invoke(&mut con)
+            .unwrap();
+        assert_eq!(sum, 3);
+    }
+}
+
+#[test]
+#[ignore] // Requires Redis Cluster.
+fn cluster_pipeline() {
+    let mut con = cluster();
+
+    // Pipelines aren't routed, and `ClusterConnection` refuses them.
+    let key = "{cluster_pipeline}.a";
+    for atomic in &[false, true] {
+        let mut pipe = ScriptPipeline::new();
+        if *atomic {
+            pipe.atomic();
+        }
+        let res = pipe
+            .script(&lua!(return redis.call("GET", @key)).route(&[key]))
+            .query::<(Option<String>,)>(&mut con);
+        assert!(res.is_err(), "{:?}", res);
+    }
+}
@@ -0,0 +1,126 @@
+use redis_lua::{lua, Error, Options, Script, ScriptError, ScriptFailure};
+
+async fn run_err<S: Script + Send>(script: S) -> Error {
+    let cli = redis::Client::open("redis://127.0.0.1").unwrap();
+    let mut con = cli.get_multiplexed_tokio_connection().await.unwrap();
+    script.invoke_async::<_, ()>(&mut con).await.unwrap_err()
+}
+
+#[tokio::test]
+async fn error_reply() {
+    match run_err(lua!(return redis.error_reply("MYERR failed"))).await {
+        Error::ErrorReply(e) => assert_eq!(e.message(), "MYERR failed"),
+        e => panic!("unexpected error: {:?}", e),
+    }
+}
+
+#[tokio::test]
+async fn lua_error() {
+    let line = line!() + 3;
+    let err = run_err(lua! {
+        local a = 1
+        error("oops")
+    })
+    .await;
+    match &err {
+        Error::Lua(e) => assert!(e.message().ends_with("oops"), "{}", e.message()),
+        e => panic!("unexpected error: {:?}", e),
+    }
+    assert_eq!(err.fragment(), Some(0));
+    assert_eq!(err.location().map(|l| l.line()), Some(line as usize));
+}
+
+#[tokio::test]
+async fn lua_error_fragment() {
+    let s1 = lua!(local a = 1);
+    let s2 = lua!(return redis.call("incrby", "error_lua_error_fragment", "x"));
+    let err = run_err(s1.join(s2)).await;
+    assert!(matches!(err, Error::Lua(_)), "{:?}", err);
+    assert_eq!(err.fragment(), Some(1));
+}
+
+#[derive(Debug, PartialEq, ScriptError)]
+enum TransferError {
+    InsufficientFunds { balance: i64 },
+    AccountLocked,
+    Limit(u32, String),
+}
+
+async fn run_failure<S: Script + Send + 'static>(script: S) -> ScriptFailure<TransferError> {
+    let cli = redis::Client::open("redis://127.0.0.1").unwrap();
+    let mut con = cli.get_multiplexed_tokio_connection().await.unwrap();
+    script
+        .raising::<TransferError>()
+        .invoke_async::<_, ()>(&mut con)
+        .await
+        .unwrap_err()
+}
+
+#[tokio::test]
+async fn script_error() {
+    let script = lua!(return script_error("INSUFFICIENT_FUNDS", { balance = 10 }));
+    match run_failure(script).await {
+        ScriptFailure::Script(e) => assert_eq!(e, TransferError::InsufficientFunds { balance: 10 }),
+        e => panic!("unexpected error: {:?}", e),
+    }
+    match run_failure(lua!(return script_error("ACCOUNT_LOCKED"))).await {
+        ScriptFailure::Script(e) => assert_eq!(e, TransferError::AccountLocked),
+        e => panic!("unexpected error: {:?}", e),
+    }
+    match run_failure(lua!(return script_error("LIMIT", {3, "daily"}))).await {
+        ScriptFailure::Script(e) => assert_eq!(e, TransferError::Limit(3, "daily".into())),
+        e => panic!("unexpected error: {:?}", e),
+    }
+    match run_failure(lua!(return redis.error_reply("UNKNOWN failed"))).await {
+        ScriptFailure::Error(Error::ErrorReply(_)) => {}
+        e => panic!("unexpected error: {:?}", e),
+    }
+}
+
+#[tokio::test]
+async fn script_error_join() {
+    let s1 = lua!(return script_error("ACCOUNT_LOCKED"));
+    let s2 = lua!(return 1);
+    match run_failure(s1.join(s2)).await {
+        ScriptFailure::Script(e) => assert_eq!(e, TransferError::AccountLocked),
+        e => panic!("unexpected error: {:?}", e),
+    }
+}
+
+#[tokio::test]
+async fn script_error_composed() {
+    let cli = redis::Client::open("redis://127.0.0.1").unwrap();
+    let mut con = cli.get_multiplexed_tokio_connection().await.unwrap();
+
+    let res = lua!(return script_error("ACCOUNT_LOCKED"))
+        .raising::<TransferError>()
+        .route(&["error_composed"])
+        .with_options(Options::new())
+        .invoke_async::<_, ()>(&mut con)
+        .await;
+    match res.unwrap_err() {
+        ScriptFailure::Script(e) => assert_eq!(e, TransferError::AccountLocked),
+        e => panic!("unexpected error: {:?}", e),
+    }
+
+    let res = lua!(return script_error("ACCOUNT_LOCKED"))
+        .route(&["error_composed"])
+        .with_options(Options::new())
+        .raising::<TransferError>()
+        .invoke_async::<_, ()>(&mut con)
+        .await;
+    match res.unwrap_err() {
+        ScriptFailure::Script(e) => assert_eq!(e, TransferError::AccountLocked),
+        e => panic!("unexpected error: {:?}", e),
+    }
+
+    let res = lua!(return script_error("ACCOUNT_LOCKED"))
+        .raising::<TransferError>()
+        .route(&["{a}x", "{b}y"])
+        .invoke_async::<_, ()>(&mut con)
+        .await;
+    match res.unwrap_err() {
+        ScriptFailure::Error(Error::CrossSlot(_)) => {}
+        e => panic!("unexpected error: {:?}", e),
+    }
+}
@@ -0,0 +1,123 @@
+use redis::IntoConnectionInfo;
+use redis_lua::{lua, Error, NodeResults, Primaries, Script};
+use std::collections::HashMap;
+
+fn results<T>(values: Vec<T>) -> NodeResults<T> {
+    values
+        .into_iter()
+        .enumerate()
+        .map(|(i, v)| (format!("127.0.0.1:{}", 7000 + i), Ok(v)))
+        .collect()
+}
+
+#[test]
+fn reduce() {
+    assert_eq!(results(vec![1, 2, 3]).sum::<usize>().unwrap(), 6);
+    assert_eq!(results(vec![1, 5, 3]).reduce(usize::max).unwrap(), Some(5));
+    assert_eq!(
+        results(Vec::<usize>::new()).reduce(usize::max).unwrap(),
+        None
+    );
+    assert_eq!(
+        results(vec![vec!["a"], vec![], vec!["b", "c"]])
+            .concat()
+            .unwrap(),
+        vec!["a", "b", "c"]
+    );
+
+    let merged: HashMap<_, _> = results(vec![vec![("a", 1)], vec![("b", 2)]])
+        .merge()
+        .unwrap();
+    assert_eq!(merged, vec![("a", 1), ("b", 2)].into_iter().collect());
+}
+
+#[test]
+fn error() {
+    let mut res = results(vec![1, 2]).into_inner();
+    res.push((
+        "127.0.0.1:7002".into(),
+        Err(redis::RedisError::from((redis::ErrorKind::IoError, "down")).into()),
+    ));
+    let res: NodeResults<usize> = res.into_iter().collect();
+
+    assert_eq!(res.iter().filter(|(_, r)| r.is_ok()).count(), 2);
+    assert!(res.sum::<usize>().is_err());
+}
+
+#[test]
+fn not_cluster() {
+    let info = "redis://127.0.0.1".into_connection_info().unwrap();
+    let mut con = redis::Client::open(info.clone())
+        .unwrap()
+        .get_connection()
+        .unwrap();
+
+    // `CLUSTER SLOTS` fails on a server without cluster support.
+    assert!(Primaries::connect(&mut con, &info).is_err());
+}
+
+fn primaries() -> Primaries {
+    // The server without cluster support stands for the single primary.
+    let nodes = (0..2)
+        .map(|i| {
+            let con = redis::Client::open("redis://127.0.0.1")
+                .unwrap()
+                .get_connection()
+                .unwrap();
+            (format!("node{}", i), con)
+        })
+        .collect();
+    Primaries::new(nodes)
+}
+
+#[test]
+fn reuse() {
+    let primaries = primaries();
+    let nodes: Vec<_> = primaries.nodes().collect();
+    assert_eq!(nodes, vec!["node0", "node1"]);
+
+    for _ in 0..2 {
+        let sum: usize = lua!(return 1)
+            .invoke_on_all_primaries::<_, usize>(&primaries)
+            .unwrap()
+            .sum()
+            .unwrap();
+        assert_eq!(sum, 2);
+    }
+
+    for (_, mut con) in primaries.into_inner() {
+        redis::cmd("PING").query::<()>(&mut con).unwrap();
+    }
+}
+
+#[test]
+fn routed() {
+    match lua!(return 1)
+        .route(&["a"])
+        .invoke_on_all_primaries::<_, usize>(&primaries())
+    {
+        Err(Error::Other(_)) => {}
+        res => panic!("unexpected result: {:?}", res),
+    }
+}
+
+struct Panicking;
+
+impl redis::FromRedisValue for Panicking {
+    fn from_redis_value(_: &redis::Value) -> redis::RedisResult<Self> {
+        panic!("unexpected value")
+    }
+}
+
+#[test]
+fn panicked() {
+    let primaries = primaries();
+
+    // The first invocation panics, and the poisoned connections are not used any more.
+    for _ in 0..2 {
+        let res = lua!(return 1)
+            .invoke_on_all_primaries::<_, Panicking>(&primaries)
+            .unwrap();
+        assert!(res.iter().all(|(_, res)| res.is_err()));
+    }
+}
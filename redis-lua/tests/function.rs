@@ -0,0 +1,78 @@
+// Redis functions are available since Redis 7.
+
+use redis_lua::{lua_library, Error};
+
+lua_library! {
+    name = "redis_lua_test";
+
+    fn set(key, value) {
+        return redis.call("SET", $key, cjson.encode($value))
+    }
+
+    #[no_writes]
+    fn get(key) {
+        return redis.call("GET", $key)
+    }
+
+    fn incr(by) {
+        return redis.call("INCRBY", keys[1], $by)
+    }
+
+    fn fail(tag) {
+        return redis.error_reply($tag .. " failed")
+    }
+}
+
+#[tokio::test]
+async fn call() {
+    let cli = redis::Client::open("redis://127.0.0.1").unwrap();
+    let mut con = cli.get_multiplexed_tokio_connection().await.unwrap();
+
+    redis_lua_test::LIBRARY
+        .load_async(&mut con, true)
+        .await
+        .unwrap();
+
+    let key = "function_call";
+    redis_lua_test::set(key, vec![1, 2, 3])
+        .fcall_async::<_, ()>(&mut con)
+        .await
+        .unwrap();
+    let value: String = redis_lua_test::get(key)
+        .fcall_ro_async(&mut con)
+        .await
+        .unwrap();
+    assert_eq!(value, "[1,2,3]");
+
+    let key = "{function}call_keys";
+    redis::cmd("DEL")
+        .arg(key)
+        .query_async::<_, ()>(&mut con)
+        .await
+        .unwrap();
+    let value: i64 = redis_lua_test::incr(2)
+        .route(&[key])
+        .fcall_async(&mut con)
+        .await
+        .unwrap();
+    assert_eq!(value, 2);
+
+    match redis_lua_test::incr(1)
+        .route(&["{a}x", "{b}y"])
+        .fcall_async::<_, ()>(&mut con)
+        .await
+        .unwrap_err()
+    {
+        Error::CrossSlot(_) => {}
+        e => panic!("unexpected error: {:?}", e),
+    }
+
+    match redis_lua_test::fail("MYERR")
+        .fcall_async::<_, ()>(&mut con)
+        .await
+        .unwrap_err()
+    {
+        Error::ErrorReply(e) => assert_eq!(e.message(), "MYERR failed"),
+        e => panic!("unexpected error: {:?}", e),
+    }
+}
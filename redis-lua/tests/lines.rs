@@ -0,0 +1,40 @@
+use redis_lua::{lua, Script};
+
+async fn run_err<S: Script + Send>(script: S) -> String {
+    let cli = redis::Client::open("redis://127.0.0.1").unwrap();
+    let mut con = cli.get_multiplexed_tokio_connection().await.unwrap();
+    let err = script.invoke_async::<_, ()>(&mut con).await.unwrap_err();
+    err.to_string()
+}
+
+#[tokio::test]
+async fn error_location() {
+    let line = line!() + 3;
+    let msg = run_err(lua! {
+        local a = 1
+        return redis.call("incrby", "lines_error_location", "x")
+    })
+    .await;
+    assert!(
+        msg.contains(&format!("(at {}:{})", file!(), line)),
+        "{}",
+        msg
+    );
+}
+
+#[tokio::test]
+async fn error_location_join() {
+    let s1 = lua! {
+        local a = 1
+    };
+    let line = line!() + 2;
+    let s2 = lua! {
+        return redis.call("incrby", "lines_error_location_join", "x")
+    };
+    let msg = run_err(s1.join(s2)).await;
+    assert!(
+        msg.contains(&format!("(at {}:{})", file!(), line)),
+        "{}",
+        msg
+    );
+}
@@ -0,0 +1,27 @@
+#[macro_use]
+mod util;
+
+#[tokio::test]
+async fn literal_string() {
+    test!(String { return "plain" }, "plain");
+    test!(String { return "tab\tquote\"" }, "tab\tquote\"");
+    test!(String { return "\x41\x42" }, "AB");
+    test!(String { return "\u{1F600}" }, "\u{1F600}");
+    test!(String { return "\0" .. "1" }, "\u{0}1");
+    test!(String { return r"C:\path" }, "C:\\path");
+    test!(String { return r#"say "hi""# }, "say \"hi\"");
+    test!(String { return b"bytes" }, "bytes");
+    test!(String { return 'a' }, "a");
+    test!(String { return '\'' }, "'");
+}
+
+#[tokio::test]
+async fn literal_number() {
+    test!(u64 { return 1_000_000 }, 1_000_000);
+    test!(u64 { return 10u32 + 1 }, 11);
+    test!(u64 { return 1e3f64 }, 1000);
+    test!(u64 { return 0b1010 }, 10);
+    test!(u64 { return 0o17 }, 15);
+    test!(u64 { return 0xffu8 }, 255);
+    test!(u64 { return b'a' }, 97);
+}
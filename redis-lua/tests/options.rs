@@ -0,0 +1,122 @@
+use redis::IntoConnectionInfo;
+use redis_lua::{lua, Error, Options, Script};
+use std::time::{Duration, Instant};
+
+fn killable<S: Script>(script: S) -> bool {
+    let mut info = vec![];
+    script.info(&mut info, &mut vec![]);
+    info.iter().all(|info| info.killable())
+}
+
+#[test]
+fn killable_scripts() {
+    assert!(killable(lua!(return 1)));
+    assert!(killable(lua!(return redis.call("get", "a"))));
+    assert!(killable(lua!(return redis.pcall("HGETALL", $key)).key("a")));
+
+    assert!(!killable(lua!(return redis.call("SET", "a", 1))));
+    assert!(!killable(lua!(return redis.call($cmd, "a")).cmd("GET")));
+    assert!(!killable(
+        lua!(return 1).join(lua!(return redis.call("DEL", "a")))
+    ));
+}
+
+/// Sets a server config, restoring the previous value on drop not to affect the other tests.
+struct Config {
+    con: redis::Connection,
+    name: &'static str,
+    old: String,
+}
+
+impl Config {
+    fn set(name: &'static str, value: &str) -> Self {
+        let cli = redis::Client::open("redis://127.0.0.1").unwrap();
+        let mut con = cli.get_connection().unwrap();
+
+        let (_, old): (String, String) = redis::cmd("CONFIG")
+            .arg("GET")
+            .arg(name)
+            .query(&mut con)
+            .unwrap();
+        redis::cmd("CONFIG")
+            .arg("SET")
+            .arg(name)
+            .arg(value)
+            .query::<()>(&mut con)
+            .unwrap();
+
+        Self { con, name, old }
+    }
+}
+
+impl Drop for Config {
+    fn drop(&mut self) {
+        let _ = redis::cmd("CONFIG")
+            .arg("SET")
+            .arg(self.name)
+            .arg(&self.old)
+            .query::<()>(&mut self.con);
+    }
+}
+
+fn busy_loop() -> impl Script + Send {
+    lua! {
+        local i = 0
+        while true do
+            i = i + 1
+        end
+        return i
+    }
+}
+
+fn options() -> Options {
+    Options::new().deadline(
+        Duration::from_millis(200),
+        "redis://127.0.0.1".into_connection_info().unwrap(),
+    )
+}
+
+fn assert_killed<T: std::fmt::Debug>(start: Instant, res: Result<T, Error>) {
+    assert!(matches!(res, Err(Error::Killed(_))), "{:?}", res);
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+// The scripts run one by one in a single test, as a running script blocks the server.
+#[tokio::test]
+async fn deadline() {
+    // Let the server accept `SCRIPT KILL` soon.
+    let _config = Config::set("lua-time-limit", "100");
+
+    let cli = redis::Client::open("redis://127.0.0.1").unwrap();
+    let mut con = cli.get_connection().unwrap();
+
+    let start = Instant::now();
+    let res = busy_loop()
+        .with_options(options())
+        .invoke::<usize>(&mut con);
+    assert_killed(start, res);
+
+    // The options are kept by the other wrappers.
+    let start = Instant::now();
+    let res = busy_loop()
+        .with_options(options())
+        .route(&["options_deadline"])
+        .invoke::<usize>(&mut con);
+    assert_killed(start, res);
+
+    let start = Instant::now();
+    let res = lua!(local a = 1)
+        .join(busy_loop())
+        .with_options(options())
+        .invoke::<usize>(&mut con);
+    assert_killed(start, res);
+
+    let mut con = cli.get_multiplexed_tokio_connection().await.unwrap();
+    let start = Instant::now();
+    let res = busy_loop()
+        .route(&["options_deadline"])
+        .with_options(options())
+        .invoke_async::<_, usize>(&mut con)
+        .await;
+    assert_killed(start, res);
+}
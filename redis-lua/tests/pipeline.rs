@@ -0,0 +1,140 @@
+use redis_lua::{lua, Error, Script, ScriptPipeline};
+
+async fn connect() -> redis::aio::MultiplexedConnection {
+    let cli = redis::Client::open("redis://127.0.0.1").unwrap();
+    cli.get_multiplexed_tokio_connection().await.unwrap()
+}
+
+#[tokio::test]
+async fn scripts_and_commands() {
+    let mut con = connect().await;
+
+    let x = 10;
+    let s1 = lua!(return redis.call("GET", "pipeline_scripts_and_commands") + @x);
+    let s2 = lua!(local a = 1).join(lua!(return $y + 2).y(3));
+
+    let (a, b): (usize, usize) = ScriptPipeline::new()
+        .cmd(
+            redis::cmd("SET")
+                .arg("pipeline_scripts_and_commands")
+                .arg(1)
+                .clone(),
+        )
+        .ignore()
+        .script(&s1)
+        .script(&s2)
+        .query_async(&mut con)
+        .await
+        .unwrap();
+    assert_eq!((a, b), (11, 5));
+}
+
+#[tokio::test]
+async fn load_scripts() {
+    let mut con = connect().await;
+
+    redis::cmd("SCRIPT")
+        .arg("FLUSH")
+        .query_async::<_, ()>(&mut con)
+        .await
+        .unwrap();
+
+    let (a, b): (usize, usize) = ScriptPipeline::new()
+        .atomic()
+        .script(&lua!(return $x + 1).x(1))
+        .script(&lua!(return $x + 1).x(2))
+        .query_async(&mut con)
+        .await
+        .unwrap();
+    assert_eq!((a, b), (2, 3));
+}
+
+#[tokio::test]
+async fn repeated_script() {
+    let mut con = connect().await;
+
+    redis::cmd("SCRIPT")
+        .arg("FLUSH")
+        .query_async::<_, ()>(&mut con)
+        .await
+        .unwrap();
+
+    // The second one runs by `EVALSHA` the script cached by the first one.
+    let script = |x: usize| lua!(return $x * 2).x(x);
+    let (a, b): (usize, usize) = ScriptPipeline::new()
+        .script(&script(1))
+        .script(&script(2))
+        .query_async(&mut con)
+        .await
+        .unwrap();
+    assert_eq!((a, b), (2, 4));
+}
+
+#[tokio::test]
+async fn routed() {
+    let mut con = connect().await;
+
+    redis::cmd("SCRIPT")
+        .arg("FLUSH")
+        .query_async::<_, ()>(&mut con)
+        .await
+        .unwrap();
+
+    let key = "{pipeline_routed}a";
+    let (a,): (usize,) = ScriptPipeline::new()
+        .cmd(redis::cmd("SET").arg(key).arg(3).clone())
+        .ignore()
+        .script(&lua!(return redis.call("GET", @key) + 1).route(&[key]))
+        .query_async(&mut con)
+        .await
+        .unwrap();
+    assert_eq!(a, 4);
+}
+
+#[tokio::test]
+async fn run_once() {
+    let mut con = connect().await;
+
+    let key = "pipeline_run_once";
+    redis::cmd("DEL")
+        .arg(key)
+        .query_async::<_, ()>(&mut con)
+        .await
+        .unwrap();
+    redis::cmd("SCRIPT")
+        .arg("FLUSH")
+        .query_async::<_, ()>(&mut con)
+        .await
+        .unwrap();
+
+    // The script isn't cached, which must not run `INCR` twice.
+    let (a, b): (usize, usize) = ScriptPipeline::new()
+        .cmd(redis::cmd("INCR").arg(key).clone())
+        .script(&lua!(return redis.call("GET", @key) + 10))
+        .query_async(&mut con)
+        .await
+        .unwrap();
+    assert_eq!((a, b), (1, 11));
+
+    let count: usize = redis::cmd("GET")
+        .arg(key)
+        .query_async(&mut con)
+        .await
+        .unwrap();
+    assert_eq!(count, 1);
+}
+
+#[tokio::test]
+async fn error() {
+    let mut con = connect().await;
+
+    let err = ScriptPipeline::new()
+        .script(&lua!(return redis.error_reply("MYERR failed")))
+        .query_async::<_, ((),)>(&mut con)
+        .await
+        .unwrap_err();
+    match err {
+        Error::ErrorReply(e) => assert_eq!(e.message(), "MYERR failed"),
+        e => panic!("unexpected error: {:?}", e),
+    }
+}
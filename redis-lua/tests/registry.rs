@@ -0,0 +1,38 @@
+use redis_lua::{check_scripts_async, lua, preload_scripts_async, scripts, Script};
+
+fn registered() -> impl Script {
+    lua!(return "registry_registered")
+}
+
+#[test]
+fn list() {
+    let line = line!() - 5;
+    let mut info = vec![];
+    registered().info(&mut info, &mut vec![]);
+
+    let s = scripts()
+        .find(|s| s.hash() == info[0].hash())
+        .expect("not registered");
+    let loc = s.location();
+    assert_eq!(loc.line(), line as usize);
+    assert_eq!(
+        s.name(),
+        format!(
+            "{} ({}:{}:{})",
+            module_path!(),
+            loc.file(),
+            loc.line(),
+            loc.column()
+        )
+    );
+    assert_eq!(s.code(), info[0].code());
+}
+
+#[tokio::test]
+async fn preload_and_check() {
+    let cli = redis::Client::open("redis://127.0.0.1").unwrap();
+    let mut con = cli.get_multiplexed_tokio_connection().await.unwrap();
+
+    preload_scripts_async(&mut con).await.unwrap();
+    assert!(check_scripts_async(&mut con).await.unwrap().is_empty());
+}
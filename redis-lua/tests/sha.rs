@@ -0,0 +1,43 @@
+#[macro_use]
+mod util;
+
+use redis_lua::{gen_script, lua, Script};
+
+fn check_hash<S: Script>(script: S) {
+    let mut info = vec![];
+    let mut args = vec![];
+    script.info(&mut info, &mut args);
+    assert_eq!(info.len(), 1);
+
+    // The script generated at compile time must be the same as the one at runtime.
+    let runtime = gen_script(&info);
+    assert_eq!(runtime.get_hash(), info[0].hash());
+}
+
+#[test]
+fn compile_time_hash() {
+    check_hash(lua!(return 1));
+
+    let x = 1;
+    check_hash(lua!(return @x + 1));
+    check_hash(lua!(return $y + 2).y(3));
+}
+
+#[tokio::test]
+async fn joined() {
+    // The joined script is generated once and memoized.
+    for i in 0..3 {
+        let s1 = lua!(local a = 1);
+        let s2 = lua!(return $x);
+        assert_eq!(util::run::<usize, _>(s1.join(s2.x(i))).await, i);
+    }
+
+    // Packed arguments don't change the script.
+    let v: Vec<u8> = vec![];
+    let s1 = lua!(local a = 1);
+    let s2 = lua!(return $x);
+    assert_eq!(
+        util::run::<Vec<usize>, _>(s1.join(s2.x(v))).await,
+        Vec::<usize>::new()
+    );
+}
@@ -0,0 +1,72 @@
+use futures::prelude::*;
+use redis_lua::{lua, transaction, transaction_async, Error};
+
+#[test]
+fn watch_and_retry() {
+    let cli = redis::Client::open("redis://127.0.0.1").unwrap();
+    let mut con = cli.get_connection().unwrap();
+    let mut other = cli.get_connection().unwrap();
+
+    let key = "transaction_watch_and_retry";
+    redis::cmd("SET")
+        .arg(key)
+        .arg(1)
+        .query::<()>(&mut con)
+        .unwrap();
+
+    let mut tries = 0;
+    let (a, b): (usize, usize) = transaction(&mut con, &[key], |con, pipe| {
+        let current: usize = redis::cmd("GET").arg(key).query(con)?;
+        tries += 1;
+        if tries == 1 {
+            // Abort the first try by modifying the watched key.
+            redis::cmd("SET").arg(key).arg(10).query::<()>(&mut other)?;
+        }
+        pipe.cmd(redis::cmd("SET").arg(key).arg(current + 1).clone())
+            .ignore()
+            .script(&lua!(return redis.call("GET", @key) + 0))
+            .cmd(redis::cmd("INCR").arg(key).clone());
+        Ok(())
+    })
+    .unwrap();
+
+    assert_eq!(tries, 2);
+    assert_eq!((a, b), (11, 12));
+}
+
+#[test]
+fn abort() {
+    let cli = redis::Client::open("redis://127.0.0.1").unwrap();
+    let mut con = cli.get_connection().unwrap();
+
+    let key = "transaction_abort";
+    let err = transaction::<_, _, (), _>(&mut con, &[key], |_, pipe| {
+        pipe.script(&lua!(return 1));
+        Err(redis::RedisError::from((redis::ErrorKind::ClientError, "abort")).into())
+    })
+    .unwrap_err();
+    assert!(matches!(err, Error::Other(_)), "{:?}", err);
+}
+
+#[tokio::test]
+async fn load_scripts_async() {
+    let cli = redis::Client::open("redis://127.0.0.1").unwrap();
+    let mut con = cli.get_async_connection().await.unwrap();
+
+    redis::cmd("SCRIPT")
+        .arg("FLUSH")
+        .query_async::<_, ()>(&mut con)
+        .await
+        .unwrap();
+
+    let key = "transaction_load_scripts_async";
+    let (a,): (usize,) = transaction_async(&mut con, &[key], |_, pipe| {
+        pipe.cmd(redis::cmd("SET").arg(key).arg(3).clone())
+            .ignore()
+            .script(&lua!(return redis.call("GET", @key) * $x).x(2));
+        async { Ok(()) }.boxed()
+    })
+    .await
+    .unwrap();
+    assert_eq!(a, 6);
+}